@@ -0,0 +1,28 @@
+use crate::commands::CommandError;
+use crate::models::{AlertChannel, AlertDelivery, AlertEvent, HealthStatus};
+use crate::services::AlertDispatcher;
+use tauri::State;
+
+// Sends a sample offline alert through a channel, which may not be saved yet,
+// so the user can check the URL, headers and template.
+#[tauri::command]
+pub async fn send_test_alert(
+    dispatcher: State<'_, AlertDispatcher>,
+    channel: AlertChannel,
+) -> Result<AlertDelivery, CommandError> {
+    let event = AlertEvent {
+        connection_id: None,
+        name: "Test connection".into(),
+        status: HealthStatus::Offline,
+        previous_status: Some(HealthStatus::Online),
+        latency_ms: None,
+        error: Some("This is a test alert from Turbo Waffle".into()),
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    };
+    Ok(dispatcher.send(&channel, &event, true).await)
+}
+
+#[tauri::command]
+pub fn get_alert_deliveries(dispatcher: State<'_, AlertDispatcher>) -> Vec<AlertDelivery> {
+    dispatcher.deliveries()
+}
@@ -1,37 +1,117 @@
-use crate::models::Connection;
-use crate::storage::ConfigStorage;
-use uuid::Uuid;
+use crate::commands::{parse_id, CommandError};
+use crate::models::{ConfigBackup, Connection, ConnectionConfig};
+use crate::services::{find_connections, validate_probe, HealthScheduler, SearchQuery};
+use crate::storage::{ConnectionRepository, SecretStorage, StorageError};
+use std::collections::HashMap;
+use tauri::State;
 
 #[tauri::command]
-pub fn get_connections() -> Result<Vec<Connection>, String> {
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
-    storage.load_connections().map_err(|e| e.to_string())
+pub fn get_connections(repository: State<'_, ConnectionRepository>) -> Vec<Connection> {
+    repository.all()
 }
 
 #[tauri::command]
-pub fn save_connection(connection: Connection) -> Result<(), String> {
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
-    storage.save_connection(connection).map_err(|e| e.to_string())
+pub fn search_connections(
+    repository: State<'_, ConnectionRepository>,
+    scheduler: State<'_, HealthScheduler>,
+    query: String,
+) -> Vec<Connection> {
+    let latest: HashMap<_, _> = scheduler
+        .latest()
+        .into_iter()
+        .map(|result| (result.connection_id, result))
+        .collect();
+
+    find_connections(&repository.all(), &latest, &SearchQuery::parse(&query))
 }
 
 #[tauri::command]
-pub fn delete_connection(id: String) -> Result<bool, String> {
-    let uuid = Uuid::parse_str(&id).map_err(|e| e.to_string())?;
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
-    storage.delete_connection(uuid).map_err(|e| e.to_string())
+pub fn save_connection(
+    repository: State<'_, ConnectionRepository>,
+    scheduler: State<'_, HealthScheduler>,
+    mut connection: Connection,
+) -> Result<(), CommandError> {
+    if let ConnectionConfig::Service {
+        probe: Some(probe), ..
+    } = &connection.config
+    {
+        validate_probe(probe).map_err(CommandError::InvalidProbe)?;
+    }
+
+    let secrets = SecretStorage::new()?;
+    let id = connection.id;
+    let stored_kind = repository
+        .get(id)
+        .and_then(|stored| stored.auth().map(std::mem::discriminant));
+
+    // A missing secret on an authenticated connection means "keep the stored
+    // one", but only for the same kind of auth: a Basic password is no bearer
+    // token or private key. Removing auth altogether also drops its secret.
+    match (connection.take_auth_secret(), connection.auth()) {
+        (Some(secret), Some(_)) => secrets.set(id, secret),
+        (_, None) => secrets.remove(id),
+        (None, Some(auth)) if stored_kind != Some(std::mem::discriminant(auth)) => {
+            secrets.remove(id)
+        }
+        (None, Some(_)) => Ok(()),
+    }?;
+
+    repository.save(connection)?;
+    scheduler.check_soon(id);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn reorder_connections(ids: Vec<String>) -> Result<(), String> {
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
-    let mut connections = storage.load_connections().map_err(|e| e.to_string())?;
+pub fn delete_connection(
+    repository: State<'_, ConnectionRepository>,
+    scheduler: State<'_, HealthScheduler>,
+    id: String,
+) -> Result<bool, CommandError> {
+    let uuid = parse_id(&id)?;
+    let deleted = repository.delete(uuid)?;
+    SecretStorage::new()?.remove(uuid)?;
+    scheduler.forget(uuid);
+    Ok(deleted)
+}
 
-    for (index, id) in ids.iter().enumerate() {
-        let uuid = Uuid::parse_str(id).map_err(|e| e.to_string())?;
-        if let Some(conn) = connections.iter_mut().find(|c| c.id == uuid) {
-            conn.order = index as i32;
-        }
-    }
+#[tauri::command]
+pub fn reorder_connections(
+    repository: State<'_, ConnectionRepository>,
+    ids: Vec<String>,
+) -> Result<(), CommandError> {
+    let uuids = ids
+        .iter()
+        .map(|id| parse_id(id))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    storage.save_connections(&connections).map_err(|e| e.to_string())
+    repository.reorder(&uuids).map_err(CommandError::from)
+}
+
+// Set when connections.json could not be loaded at startup; the UI offers the
+// backups to restore instead.
+#[tauri::command]
+pub fn get_config_error(repository: State<'_, ConnectionRepository>) -> Option<CommandError> {
+    repository
+        .load_error()
+        .map(|reason| CommandError::from(StorageError::LoadFailed(reason)))
+}
+
+#[tauri::command]
+pub fn list_config_backups(
+    repository: State<'_, ConnectionRepository>,
+) -> Result<Vec<ConfigBackup>, CommandError> {
+    repository.list_backups().map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn restore_config_backup(
+    repository: State<'_, ConnectionRepository>,
+    scheduler: State<'_, HealthScheduler>,
+    name: String,
+) -> Result<Vec<Connection>, CommandError> {
+    let connections = repository.restore_backup(&name)?;
+    for connection in &connections {
+        scheduler.check_soon(connection.id);
+    }
+    Ok(connections)
 }
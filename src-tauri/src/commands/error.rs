@@ -0,0 +1,137 @@
+use crate::services::{RemoteDesktopError, SerialError, TerminalError, WolError};
+use crate::storage::StorageError;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+use uuid::Uuid;
+
+// Every command returns this so the UI can branch on `code` (for example to
+// suggest installing a terminal or setting a MAC address) instead of parsing
+// messages.
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("Invalid connection id \"{0}\"")]
+    InvalidId(String),
+    #[error("Connection not found")]
+    ConnectionNotFound,
+    #[error("Group not found")]
+    GroupNotFound,
+    #[error("Invalid probe: {0}")]
+    InvalidProbe(String),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Wol(#[from] WolError),
+    #[error(transparent)]
+    Terminal(#[from] TerminalError),
+    #[error(transparent)]
+    RemoteDesktop(#[from] RemoteDesktopError),
+    #[error(transparent)]
+    Serial(#[from] SerialError),
+    #[error("This connection has nothing to open")]
+    NotOpenable,
+    #[error("Could not open {target}")]
+    OpenFailed { target: String, reason: String },
+}
+
+impl CommandError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidId(_) => "invalid_id",
+            Self::ConnectionNotFound => "connection_not_found",
+            Self::GroupNotFound => "group_not_found",
+            Self::InvalidProbe(_) => "invalid_probe",
+            Self::Storage(e) => match e {
+                StorageError::NoConfigDir => "no_config_dir",
+                StorageError::Io(_) => "storage_io",
+                StorageError::Json(_) => "storage_corrupt",
+                StorageError::Decrypt => "secrets_unreadable",
+                StorageError::SecretsKeyMissing => "secrets_key_missing",
+                StorageError::BackupNotFound(_) => "backup_not_found",
+                StorageError::UnsupportedVersion(_) => "config_too_new",
+                StorageError::LoadFailed(_) => "config_unreadable",
+            },
+            Self::Wol(e) => match e {
+                WolError::InvalidMacAddress => "invalid_mac_address",
+                WolError::MacNotFound => "mac_not_found",
+                WolError::PacketCreation(_) | WolError::SendFailed(_) => "wol_send_failed",
+                WolError::NotEnabled => "wol_not_enabled",
+                WolError::NoMacAddress => "missing_mac_address",
+                WolError::Unsupported => "wol_unsupported",
+            },
+            Self::Terminal(e) | Self::Serial(SerialError::Terminal(e)) => match e {
+                TerminalError::NoTerminalFound => "no_terminal",
+                TerminalError::LaunchFailed(_) => "terminal_launch_failed",
+            },
+            Self::RemoteDesktop(e) => match e {
+                RemoteDesktopError::NoClientFound(_) => "no_remote_client",
+                RemoteDesktopError::LaunchFailed { .. } => "remote_launch_failed",
+            },
+            Self::Serial(SerialError::NoClientFound) => "no_serial_client",
+            Self::NotOpenable => "not_openable",
+            Self::OpenFailed { .. } => "open_failed",
+        }
+    }
+
+    pub fn details(&self) -> Option<String> {
+        match self {
+            Self::Storage(StorageError::Io(e)) => Some(e.to_string()),
+            Self::Storage(StorageError::Json(e)) => Some(e.to_string()),
+            Self::Wol(WolError::SendFailed(e)) => Some(e.to_string()),
+            Self::Wol(WolError::PacketCreation(reason))
+            | Self::Terminal(TerminalError::LaunchFailed(reason))
+            | Self::Serial(SerialError::Terminal(TerminalError::LaunchFailed(reason)))
+            | Self::RemoteDesktop(RemoteDesktopError::LaunchFailed { reason, .. })
+            | Self::Storage(StorageError::LoadFailed(reason))
+            | Self::OpenFailed { reason, .. } => Some(reason.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorPayload {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorPayload {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub fn parse_id(id: &str) -> Result<Uuid, CommandError> {
+    Uuid::parse_str(id).map_err(|_| CommandError::InvalidId(id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serializes_code_message_and_details() {
+        // arrange
+        let error = CommandError::from(TerminalError::LaunchFailed("exit status 127".into()));
+
+        // act
+        let value = serde_json::to_value(&error).unwrap();
+
+        // assert
+        assert_eq!(
+            value,
+            json!({
+                "code": "terminal_launch_failed",
+                "message": "Failed to launch terminal: exit status 127",
+                "details": "exit status 127",
+            })
+        );
+    }
+}
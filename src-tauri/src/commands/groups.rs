@@ -0,0 +1,99 @@
+use crate::commands::{parse_id, CommandError};
+use crate::models::Group;
+use crate::storage::ConnectionRepository;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_groups(repository: State<'_, ConnectionRepository>) -> Vec<Group> {
+    repository.groups()
+}
+
+#[tauri::command]
+pub fn create_group(
+    repository: State<'_, ConnectionRepository>,
+    name: String,
+    icon: Option<String>,
+) -> Result<Group, CommandError> {
+    let order = repository
+        .groups()
+        .iter()
+        .map(|g| g.order + 1)
+        .max()
+        .unwrap_or(0);
+    let group = Group {
+        icon,
+        ..Group::new(name, order)
+    };
+
+    repository.save_group(group.clone())?;
+    Ok(group)
+}
+
+// Saves name, icon and collapsed state; order is changed with
+// `reorder_groups`.
+#[tauri::command]
+pub fn update_group(
+    repository: State<'_, ConnectionRepository>,
+    group: Group,
+) -> Result<(), CommandError> {
+    let existing = repository
+        .groups()
+        .into_iter()
+        .find(|g| g.id == group.id)
+        .ok_or(CommandError::GroupNotFound)?;
+
+    repository
+        .save_group(Group {
+            order: existing.order,
+            ..group
+        })
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn delete_group(
+    repository: State<'_, ConnectionRepository>,
+    id: String,
+) -> Result<bool, CommandError> {
+    let uuid = parse_id(&id)?;
+    repository.delete_group(uuid).map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn reorder_groups(
+    repository: State<'_, ConnectionRepository>,
+    ids: Vec<String>,
+) -> Result<(), CommandError> {
+    let uuids = ids
+        .iter()
+        .map(|id| parse_id(id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    repository
+        .reorder_groups(&uuids)
+        .map_err(CommandError::from)
+}
+
+// Moves a connection into a group (or out of all groups when `group_id` is
+// missing), at `index` within it or at the end.
+#[tauri::command]
+pub fn move_connection(
+    repository: State<'_, ConnectionRepository>,
+    id: String,
+    group_id: Option<String>,
+    index: Option<usize>,
+) -> Result<(), CommandError> {
+    let uuid = parse_id(&id)?;
+    let group_id = group_id.as_deref().map(parse_id).transpose()?;
+    if let Some(group_id) = group_id {
+        if !repository.groups().iter().any(|g| g.id == group_id) {
+            return Err(CommandError::GroupNotFound);
+        }
+    }
+
+    if repository.move_connection(uuid, group_id, index)? {
+        Ok(())
+    } else {
+        Err(CommandError::ConnectionNotFound)
+    }
+}
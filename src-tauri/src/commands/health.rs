@@ -1,30 +1,66 @@
+use crate::commands::{parse_id, CommandError};
 use crate::models::HealthResult;
-use crate::services::check_connection_health;
-use crate::storage::ConfigStorage;
-use uuid::Uuid;
+use crate::services::{
+    check_connection_health, check_connections_batch, BatchOptions, CheckOrigin, HealthBatches,
+    HealthScheduler, HEALTH_CHECKED_EVENT,
+};
+use crate::storage::ConnectionRepository;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
 
 #[tauri::command]
-pub async fn check_health(id: String) -> Result<HealthResult, String> {
-    let uuid = Uuid::parse_str(&id).map_err(|e| e.to_string())?;
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
+pub async fn check_health(
+    app: AppHandle,
+    repository: State<'_, ConnectionRepository>,
+    scheduler: State<'_, HealthScheduler>,
+    id: String,
+) -> Result<HealthResult, CommandError> {
+    let uuid = parse_id(&id)?;
 
-    let connection = storage
-        .get_connection(uuid)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Connection not found".to_string())?;
+    let connection = repository
+        .get(uuid)
+        .ok_or(CommandError::ConnectionNotFound)?;
 
-    Ok(check_connection_health(&connection).await)
+    let result = check_connection_health(&connection).await;
+    scheduler.report(&app, result.clone(), CheckOrigin::Manual);
+    Ok(result)
 }
 
 #[tauri::command]
-pub async fn check_all_health() -> Result<Vec<HealthResult>, String> {
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
-    let connections = storage.load_connections().map_err(|e| e.to_string())?;
+pub fn get_health_statuses(scheduler: State<'_, HealthScheduler>) -> Vec<HealthResult> {
+    scheduler.latest()
+}
+
+#[tauri::command]
+pub async fn check_all_health(
+    app: AppHandle,
+    repository: State<'_, ConnectionRepository>,
+    scheduler: State<'_, HealthScheduler>,
+    batches: State<'_, HealthBatches>,
+    concurrency: Option<usize>,
+    deadline_secs: Option<u64>,
+) -> Result<Vec<HealthResult>, CommandError> {
+    let connections = repository.all();
 
-    let mut results = Vec::new();
-    for connection in connections {
-        results.push(check_connection_health(&connection).await);
+    let mut options = BatchOptions::default();
+    if let Some(concurrency) = concurrency {
+        options.concurrency = concurrency;
+    }
+    if let Some(secs) = deadline_secs {
+        options.deadline = Duration::from_secs(secs);
     }
 
+    let cancelled = batches.begin();
+    let results = check_connections_batch(connections, options, cancelled, |result| {
+        let _ = app.emit(HEALTH_CHECKED_EVENT, result);
+        scheduler.report(&app, result.clone(), CheckOrigin::Manual);
+    })
+    .await;
+
     Ok(results)
 }
+
+#[tauri::command]
+pub fn cancel_health_checks(batches: State<'_, HealthBatches>) {
+    batches.cancel();
+}
@@ -0,0 +1,28 @@
+use crate::commands::{parse_id, CommandError};
+use crate::models::{HealthStats, HistoryWindow, Outage};
+use crate::services::{find_outages, window_stats};
+use crate::storage::HistoryStorage;
+
+#[tauri::command]
+pub fn get_health_stats(id: String) -> Result<Vec<HealthStats>, CommandError> {
+    let uuid = parse_id(&id)?;
+    let history = HistoryStorage::new()?;
+    let entries = history.load(uuid)?;
+    let now = chrono::Utc::now();
+
+    Ok(HistoryWindow::ALL
+        .iter()
+        .map(|window| window_stats(&entries, *window, now))
+        .collect())
+}
+
+#[tauri::command]
+pub fn get_outages(id: String, window: Option<HistoryWindow>) -> Result<Vec<Outage>, CommandError> {
+    let uuid = parse_id(&id)?;
+    let history = HistoryStorage::new()?;
+    let entries = history.load(uuid)?;
+    let now = chrono::Utc::now();
+    let since = now - window.unwrap_or(HistoryWindow::Week).duration();
+
+    Ok(find_outages(&entries, since, now))
+}
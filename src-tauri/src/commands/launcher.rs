@@ -1,31 +1,104 @@
+use crate::commands::{parse_id, CommandError};
 use crate::models::ConnectionConfig;
-use crate::services::open_ssh_in_terminal;
-use crate::storage::ConfigStorage;
+use crate::services::{
+    launch_command, mysql_args, open_in_terminal, open_rdp, open_serial, open_ssh_in_terminal,
+    open_vnc, psql_args, redis_cli_args, RdpSession,
+};
+use crate::storage::ConnectionRepository;
+use tauri::Manager;
 use tauri_plugin_opener::OpenerExt;
-use uuid::Uuid;
 
 #[tauri::command]
-pub async fn open_connection(app: tauri::AppHandle, id: String) -> Result<(), String> {
-    let uuid = Uuid::parse_str(&id).map_err(|e| e.to_string())?;
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
-
-    let connection = storage
-        .get_connection(uuid)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Connection not found".to_string())?;
+pub async fn open_connection(app: tauri::AppHandle, id: String) -> Result<(), CommandError> {
+    let uuid = parse_id(&id)?;
+    let connection = app
+        .state::<ConnectionRepository>()
+        .get(uuid)
+        .ok_or(CommandError::ConnectionNotFound)?;
 
     match &connection.config {
         ConnectionConfig::Website { url, .. } => {
             app.opener()
                 .open_url(url, None::<&str>)
-                .map_err(|e| e.to_string())?;
-            Ok(())
+                .map_err(|e| CommandError::OpenFailed {
+                    target: url.clone(),
+                    reason: e.to_string(),
+                })
         }
         ConnectionConfig::Ssh {
             host,
             port,
             username,
             ..
-        } => open_ssh_in_terminal(username, host, *port).map_err(|e| e.to_string()),
+        } => open_ssh_in_terminal(username, host, *port).map_err(CommandError::from),
+        ConnectionConfig::Rdp {
+            host,
+            port,
+            username,
+            domain,
+            resolution,
+            fullscreen,
+            gateway,
+        } => open_rdp(&RdpSession {
+            host,
+            port: *port,
+            username: username.as_deref(),
+            domain: domain.as_deref(),
+            resolution: resolution.as_deref(),
+            fullscreen: *fullscreen,
+            gateway: gateway.as_deref(),
+        })
+        .await
+        .map_err(CommandError::from),
+        ConnectionConfig::Vnc {
+            host,
+            port,
+            view_only,
+        } => open_vnc(host, *port, *view_only).map_err(CommandError::from),
+        ConnectionConfig::Postgres {
+            host,
+            port,
+            username,
+            database,
+        } => open_in_terminal(
+            "psql",
+            &psql_args(host, *port, username.as_deref(), database.as_deref()),
+        )
+        .map_err(CommandError::from),
+        ConnectionConfig::Mysql {
+            host,
+            port,
+            username,
+            database,
+        } => open_in_terminal(
+            "mysql",
+            &mysql_args(host, *port, username.as_deref(), database.as_deref()),
+        )
+        .map_err(CommandError::from),
+        ConnectionConfig::Redis {
+            host,
+            port,
+            database,
+        } => open_in_terminal("redis-cli", &redis_cli_args(host, *port, *database))
+            .map_err(CommandError::from),
+        ConnectionConfig::Service { .. } => Err(CommandError::NotOpenable),
+        ConnectionConfig::Command {
+            command,
+            working_dir,
+            env,
+            ..
+        } => launch_command(command, working_dir.as_deref(), env).map_err(|e| {
+            CommandError::OpenFailed {
+                target: command.clone(),
+                reason: e.to_string(),
+            }
+        }),
+        ConnectionConfig::Serial {
+            device,
+            baud_rate,
+            parity,
+            stop_bits,
+            ..
+        } => open_serial(device, *baud_rate, *parity, *stop_bits).map_err(CommandError::from),
     }
 }
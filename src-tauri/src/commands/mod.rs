@@ -1,9 +1,19 @@
+pub mod alerts;
 pub mod connections;
+pub mod error;
+pub mod groups;
 pub mod health;
+pub mod history;
 pub mod launcher;
+pub mod settings;
 pub mod wol;
 
+pub use alerts::*;
 pub use connections::*;
+pub use error::*;
+pub use groups::*;
 pub use health::*;
+pub use history::*;
 pub use launcher::*;
+pub use settings::*;
 pub use wol::*;
@@ -0,0 +1,48 @@
+use crate::commands::CommandError;
+use crate::models::AppSettings;
+use crate::storage::{ConnectionRepository, SecretStorage};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_settings(repository: State<'_, ConnectionRepository>) -> AppSettings {
+    let mut settings = repository.settings();
+    if let Ok(secrets) = SecretStorage::new() {
+        let _ = secrets.load_alert_headers(&mut settings.alert_channels);
+    }
+    settings
+}
+
+#[tauri::command]
+pub fn save_settings(
+    repository: State<'_, ConnectionRepository>,
+    mut settings: AppSettings,
+) -> Result<(), CommandError> {
+    let secrets = SecretStorage::new()?;
+    secrets.store_alert_headers(&mut settings.alert_channels)?;
+    for removed in repository
+        .settings()
+        .alert_channels
+        .iter()
+        .filter(|old| !settings.alert_channels.iter().any(|c| c.id == old.id))
+    {
+        secrets.remove(removed.id)?;
+    }
+
+    repository.save_settings(settings)?;
+    Ok(())
+}
+
+// Earlier versions kept webhook headers in connections.json; they are moved
+// out the first time the app starts with this one.
+pub fn move_alert_headers_to_secrets(
+    repository: &ConnectionRepository,
+) -> Result<(), CommandError> {
+    let mut settings = repository.settings();
+    if settings.alert_channels.iter().all(|c| c.headers.is_empty()) {
+        return Ok(());
+    }
+
+    SecretStorage::new()?.store_alert_headers(&mut settings.alert_channels)?;
+    repository.save_settings(settings)?;
+    Ok(())
+}
@@ -1,17 +1,19 @@
+use crate::commands::{parse_id, CommandError};
 use crate::models::ConnectionConfig;
-use crate::services::{lookup_mac_address, send_wol_packet};
-use crate::storage::ConfigStorage;
-use uuid::Uuid;
+use crate::services::{lookup_mac_address, send_wol_packet, WolError};
+use crate::storage::ConnectionRepository;
+use tauri::State;
 
 #[tauri::command]
-pub fn send_wol(id: String) -> Result<(), String> {
-    let uuid = Uuid::parse_str(&id).map_err(|e| e.to_string())?;
-    let storage = ConfigStorage::new().map_err(|e| e.to_string())?;
+pub fn send_wol(
+    repository: State<'_, ConnectionRepository>,
+    id: String,
+) -> Result<(), CommandError> {
+    let uuid = parse_id(&id)?;
 
-    let connection = storage
-        .get_connection(uuid)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Connection not found".to_string())?;
+    let connection = repository
+        .get(uuid)
+        .ok_or(CommandError::ConnectionNotFound)?;
 
     match &connection.config {
         ConnectionConfig::Ssh {
@@ -21,20 +23,18 @@ pub fn send_wol(id: String) -> Result<(), String> {
             ..
         } => {
             if !wol_enabled {
-                return Err("Wake-on-LAN is not enabled for this connection".to_string());
+                return Err(WolError::NotEnabled.into());
             }
 
-            let mac = mac_address
-                .as_ref()
-                .ok_or_else(|| "No MAC address configured".to_string())?;
+            let mac = mac_address.as_ref().ok_or(WolError::NoMacAddress)?;
 
-            send_wol_packet(mac, broadcast_addr.as_deref()).map_err(|e| e.to_string())
+            send_wol_packet(mac, broadcast_addr.as_deref()).map_err(CommandError::from)
         }
-        _ => Err("Wake-on-LAN is only supported for SSH connections".to_string()),
+        _ => Err(WolError::Unsupported.into()),
     }
 }
 
 #[tauri::command]
-pub fn lookup_mac(host: String) -> Result<String, String> {
-    lookup_mac_address(&host).map_err(|e| e.to_string())
+pub fn lookup_mac(host: String) -> Result<String, CommandError> {
+    lookup_mac_address(&host).map_err(CommandError::from)
 }
@@ -2,85 +2,55 @@ pub mod commands;
 pub mod models;
 pub mod services;
 pub mod storage;
+mod tray;
 
 use commands::{
-    check_all_health, check_health, delete_connection, get_connections, lookup_mac,
-    open_connection, reorder_connections, save_connection, send_wol,
+    cancel_health_checks, check_all_health, check_health, create_group, delete_connection,
+    delete_group, get_alert_deliveries, get_config_error, get_connections, get_groups,
+    get_health_stats, get_health_statuses, get_outages, get_settings, list_config_backups,
+    lookup_mac, move_alert_headers_to_secrets, move_connection, open_connection,
+    reorder_connections, reorder_groups, restore_config_backup, save_connection, save_settings,
+    search_connections, send_test_alert, send_wol, update_group,
 };
-use storage::ConfigStorage;
-use tauri::menu::MenuBuilder;
+use services::{AlertDispatcher, HealthBatches, HealthScheduler};
+use storage::{ConnectionRepository, HistoryStorage};
 use tauri::tray::TrayIconBuilder;
-use tauri::{Manager, Runtime, WindowEvent};
-
-fn build_tray_menu<R: Runtime, M: Manager<R>>(manager: &M) -> tauri::Result<tauri::menu::Menu<R>> {
-    let mut connections = ConfigStorage::new()
-        .and_then(|storage| storage.load_connections())
-        .unwrap_or_default();
-
-    connections.sort_by(|a, b| {
-        a.order
-            .cmp(&b.order)
-            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
-    });
-
-    let mut builder = MenuBuilder::new(manager);
-
-    if connections.is_empty() {
-        builder = builder.text("connections_empty", "No connections");
-    } else {
-        for connection in connections {
-            let label = connection.name;
-            let id = format!("connect:{}", connection.id);
-            builder = builder.text(id, label);
-        }
-    }
-
-    builder
-        .separator()
-        .text("show_main", "Open Turbo Waffle")
-        .separator()
-        .text("exit", "Exit")
-        .build()
-}
+use tauri::{Manager, WindowEvent};
+use tray::{handle_menu_event, watch_tray, TrayMenu, TRAY_ID};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
-            let menu = build_tray_menu(app.handle())?;
+            // Only a missing config directory stops startup; an unreadable
+            // connections.json is reported through `get_config_error`.
+            let repository = ConnectionRepository::load()?;
+            let _ = move_alert_headers_to_secrets(&repository);
+            app.manage(repository);
+
+            let tray_menu = TrayMenu::build(app.handle())?;
             let icon = tauri::include_image!("icons/tray-32.png");
 
-            TrayIconBuilder::with_id("main_tray")
+            TrayIconBuilder::with_id(TRAY_ID)
                 .icon(icon)
                 .icon_as_template(true)
-                .menu(&menu)
+                .menu(&tray_menu.menu)
                 .show_menu_on_left_click(true)
                 .build(app)?;
 
-            Ok(())
-        })
-        .on_menu_event(|app, event| {
-            if event.id() == "exit" {
-                app.exit(0);
-                return;
-            }
-
-            if event.id() == "show_main" {
-                if let Some(window) = app.get_webview_window("main") {
-                    let _ = window.show();
-                    let _ = window.set_focus();
-                }
-                return;
+            let mut scheduler = HealthScheduler::new();
+            if let Ok(history) = HistoryStorage::new() {
+                scheduler = scheduler.with_history(history);
             }
+            scheduler.start(app.handle().clone());
+            app.manage(scheduler);
+            app.manage(HealthBatches::new());
+            app.manage(AlertDispatcher::new());
+            watch_tray(app.handle().clone(), tray_menu);
 
-            if let Some(id) = event.id().0.strip_prefix("connect:") {
-                let id = id.to_string();
-                let handle = app.app_handle().clone();
-                tauri::async_runtime::spawn(async move {
-                    let _ = open_connection(handle, id).await;
-                });
-            }
+            Ok(())
         })
+        .on_menu_event(handle_menu_event)
         .on_window_event(|window, event| {
             if let WindowEvent::CloseRequested { api, .. } = event {
                 api.prevent_close();
@@ -89,13 +59,33 @@ pub fn run() {
         })
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .invoke_handler(tauri::generate_handler![
             get_connections,
+            search_connections,
             save_connection,
             delete_connection,
             reorder_connections,
+            get_groups,
+            create_group,
+            update_group,
+            delete_group,
+            reorder_groups,
+            move_connection,
+            get_config_error,
+            list_config_backups,
+            restore_config_backup,
             check_health,
             check_all_health,
+            cancel_health_checks,
+            get_health_statuses,
+            get_health_stats,
+            get_outages,
+            get_settings,
+            save_settings,
+            send_test_alert,
+            get_alert_deliveries,
             send_wol,
             lookup_mac,
             open_connection,
@@ -0,0 +1,67 @@
+use crate::models::HealthStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+pub const DEFAULT_ALERT_TEMPLATE: &str =
+    r#"{"text": "{{name}} is {{status}} (was {{previous_status}}) {{error}}"}"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertChannel {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_template")]
+    pub body_template: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_method() -> String {
+    "POST".into()
+}
+
+fn default_template() -> String {
+    DEFAULT_ALERT_TEMPLATE.into()
+}
+
+// The values a body template can refer to as `{{placeholder}}`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvent {
+    pub connection_id: Option<Uuid>,
+    pub name: String,
+    pub status: HealthStatus,
+    pub previous_status: Option<HealthStatus>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertDelivery {
+    pub channel_id: Uuid,
+    pub channel_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<Uuid>,
+    pub attempted_at: DateTime<Utc>,
+    pub attempts: u32,
+    pub delivered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub test: bool,
+}
@@ -1,6 +1,118 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BodyAssertion {
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonAssertion {
+    pub path: String,
+    pub equals: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpCheckSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expected_status: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_contains: Option<BodyAssertion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_excludes: Option<BodyAssertion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<JsonAssertion>,
+    #[serde(default = "default_failure_status")]
+    pub on_failure: StatusPolicy,
+}
+
+fn default_failure_status() -> StatusPolicy {
+    StatusPolicy::Offline
+}
+
+impl HttpCheckSpec {
+    pub fn needs_body(&self) -> bool {
+        self.body_contains.is_some() || self.body_excludes.is_some() || self.json.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    // Text with \r, \n, \t, \\ and \xNN escapes.
+    #[default]
+    Text,
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchFormat {
+    #[default]
+    Regex,
+    Hex,
+}
+
+// Bytes to send once connected and a pattern the reply has to contain within
+// the check timeout. Either half can be left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceProbe {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send: Option<String>,
+    #[serde(default)]
+    pub send_format: PayloadFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expect: Option<String>,
+    #[serde(default)]
+    pub expect_format: MatchFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Parity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+// The secret that goes with each scheme (password, token, header value or
+// PEM private key) lives in `SecretStorage`, never in connections.json.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HttpAuth {
+    Basic {
+        username: String,
+    },
+    Bearer,
+    Header {
+        name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    ClientCert {
+        cert_path: String,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ConnectionConfig {
@@ -8,6 +120,16 @@ pub enum ConnectionConfig {
         url: String,
         #[serde(rename = "checkPath", skip_serializing_if = "Option::is_none")]
         check_path: Option<String>,
+        #[serde(rename = "strictTls", default)]
+        strict_tls: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        check: Option<Box<HttpCheckSpec>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        auth: Option<HttpAuth>,
+        // Write-only: accepted from the UI on save, moved into the secrets
+        // file and never serialised back out.
+        #[serde(rename = "authSecret", default, skip_serializing)]
+        auth_secret: Option<String>,
     },
     Ssh {
         host: String,
@@ -20,6 +142,240 @@ pub enum ConnectionConfig {
         #[serde(rename = "broadcastAddr", skip_serializing_if = "Option::is_none")]
         broadcast_addr: Option<String>,
     },
+    Rdp {
+        host: String,
+        #[serde(default = "default_rdp_port")]
+        port: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        domain: Option<String>,
+        // "WIDTHxHEIGHT"; ignored when `fullscreen` is set.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        resolution: Option<String>,
+        #[serde(default)]
+        fullscreen: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        gateway: Option<String>,
+    },
+    Vnc {
+        host: String,
+        // Display N listens on 5900 + N.
+        #[serde(default = "default_vnc_port")]
+        port: u16,
+        #[serde(rename = "viewOnly", default)]
+        view_only: bool,
+    },
+    Postgres {
+        host: String,
+        #[serde(default = "default_postgres_port")]
+        port: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        database: Option<String>,
+    },
+    Mysql {
+        host: String,
+        #[serde(default = "default_mysql_port")]
+        port: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        database: Option<String>,
+    },
+    Redis {
+        host: String,
+        #[serde(default = "default_redis_port")]
+        port: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        database: Option<u32>,
+    },
+    Service {
+        host: String,
+        port: u16,
+        #[serde(default)]
+        protocol: Transport,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        probe: Option<ServiceProbe>,
+    },
+    // Both command lines are run by `sh -c`.
+    Command {
+        command: String,
+        #[serde(rename = "workingDir", skip_serializing_if = "Option::is_none")]
+        working_dir: Option<String>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        env: BTreeMap<String, String>,
+        // Exits 0, 1 or 2 for online, degraded or offline, like a Nagios plugin.
+        #[serde(rename = "probeCommand", skip_serializing_if = "Option::is_none")]
+        probe_command: Option<String>,
+        #[serde(rename = "timeoutSecs", skip_serializing_if = "Option::is_none")]
+        timeout_secs: Option<u64>,
+    },
+    // Always eight data bits, no flow control.
+    Serial {
+        device: String,
+        #[serde(rename = "baudRate", default = "default_baud_rate")]
+        baud_rate: u32,
+        #[serde(default)]
+        parity: Parity,
+        #[serde(rename = "stopBits", default = "default_stop_bits")]
+        stop_bits: u8,
+        // Whether the health check opens the port, rather than only looking
+        // for the device.
+        #[serde(rename = "openCheck", default = "default_open_check")]
+        open_check: bool,
+        // Clears HUPCL on the port so closing it after a check leaves DTR
+        // raised. That setting outlives the app, so it is only changed when
+        // asked to.
+        #[serde(rename = "keepDtr", default)]
+        keep_dtr: bool,
+    },
+}
+
+fn default_rdp_port() -> u16 {
+    3389
+}
+
+fn default_vnc_port() -> u16 {
+    5900
+}
+
+fn default_postgres_port() -> u16 {
+    5432
+}
+
+fn default_mysql_port() -> u16 {
+    3306
+}
+
+fn default_redis_port() -> u16 {
+    6379
+}
+
+// What most switches and routers ship with on their console port.
+fn default_open_check() -> bool {
+    true
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+fn default_stop_bits() -> u8 {
+    1
+}
+
+impl ConnectionConfig {
+    // Matches the serialised `type` tag.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConnectionConfig::Website { .. } => "website",
+            ConnectionConfig::Ssh { .. } => "ssh",
+            ConnectionConfig::Rdp { .. } => "rdp",
+            ConnectionConfig::Vnc { .. } => "vnc",
+            ConnectionConfig::Postgres { .. } => "postgres",
+            ConnectionConfig::Mysql { .. } => "mysql",
+            ConnectionConfig::Redis { .. } => "redis",
+            ConnectionConfig::Service { .. } => "service",
+            ConnectionConfig::Command { .. } => "command",
+            ConnectionConfig::Serial { .. } => "serial",
+        }
+    }
+
+    // What a user would paste elsewhere to reach this connection.
+    pub fn address(&self) -> String {
+        match self {
+            ConnectionConfig::Website { url, .. } => url.clone(),
+            ConnectionConfig::Ssh { host, port: 22, .. } => host.clone(),
+            ConnectionConfig::Rdp {
+                host, port: 3389, ..
+            } => host.clone(),
+            ConnectionConfig::Ssh { host, port, .. }
+            | ConnectionConfig::Rdp { host, port, .. }
+            | ConnectionConfig::Postgres { host, port, .. }
+            | ConnectionConfig::Mysql { host, port, .. }
+            | ConnectionConfig::Redis { host, port, .. }
+            | ConnectionConfig::Service { host, port, .. } => format!("{}:{}", host, port),
+            // VNC viewers read a single colon as a display number.
+            ConnectionConfig::Vnc { host, port, .. } => match port {
+                5900 => host.clone(),
+                5901..=5999 => format!("{}:{}", host, port - 5900),
+                _ => format!("{}::{}", host, port),
+            },
+            ConnectionConfig::Command { command, .. } => command.clone(),
+            ConnectionConfig::Serial { device, .. } => device.clone(),
+        }
+    }
+
+    // Plain services are only monitored; there is no client to launch.
+    pub fn can_open(&self) -> bool {
+        !matches!(self, ConnectionConfig::Service { .. })
+    }
+
+    pub fn wol_enabled(&self) -> bool {
+        matches!(
+            self,
+            ConnectionConfig::Ssh {
+                wol_enabled: true,
+                ..
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMode {
+    #[default]
+    Auto,
+    Icmp,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusPolicy {
+    Online,
+    Degraded,
+    Offline,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HealthThresholds {
+    // Degraded above this connect or round-trip time: TCP, ICMP, protocol
+    // handshakes and service probes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warn_latency_ms: Option<u64>,
+    // The same for HTTP checks, which time a whole response and so usually
+    // need a higher limit; see `http_latency_limit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slow_response_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warn_packet_loss: Option<f64>,
+    pub redirect_status: StatusPolicy,
+    pub client_error_status: StatusPolicy,
+    pub cert_expiry_warn_days: u32,
+}
+
+impl HealthThresholds {
+    // HTTP checks use `warn_latency_ms` when they have no limit of their own.
+    pub fn http_latency_limit(&self) -> Option<u64> {
+        self.slow_response_ms.or(self.warn_latency_ms)
+    }
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            warn_latency_ms: None,
+            slow_response_ms: None,
+            warn_packet_loss: None,
+            redirect_status: StatusPolicy::Online,
+            client_error_status: StatusPolicy::Offline,
+            cert_expiry_warn_days: 14,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,7 +389,21 @@ pub struct Connection {
     pub icon_color: Option<String>,
     #[serde(default)]
     pub order: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     pub config: ConnectionConfig,
+    #[serde(default)]
+    pub check_mode: CheckMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping_count: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub thresholds: HealthThresholds,
+    #[serde(default)]
+    pub notify: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -47,9 +417,30 @@ impl Connection {
             icon: None,
             icon_color: None,
             order,
+            group_id: None,
+            tags: Vec::new(),
             config,
+            check_mode: CheckMode::default(),
+            ping_count: None,
+            check_interval_secs: None,
+            thresholds: HealthThresholds::default(),
+            notify: false,
             created_at: now.clone(),
             updated_at: now,
         }
     }
+
+    pub fn auth(&self) -> Option<&HttpAuth> {
+        match &self.config {
+            ConnectionConfig::Website { auth, .. } => auth.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn take_auth_secret(&mut self) -> Option<String> {
+        match &mut self.config {
+            ConnectionConfig::Website { auth_secret, .. } => auth_secret.take(),
+            _ => None,
+        }
+    }
 }
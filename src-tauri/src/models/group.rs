@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub collapsed: bool,
+    #[serde(default)]
+    pub order: i32,
+}
+
+impl Group {
+    pub fn new(name: String, order: i32) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            icon: None,
+            collapsed: false,
+            order,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -10,6 +11,74 @@ pub enum HealthStatus {
     Unknown,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PingStats {
+    pub sent: u16,
+    pub received: u16,
+    pub packet_loss: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jitter_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsCertificate {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsInfo {
+    pub chain: Vec<TlsCertificate>,
+    pub issuer: String,
+    pub subject_alt_names: Vec<String>,
+    pub days_until_expiry: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpTiming {
+    pub dns_ms: f64,
+    pub connect_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_ms: Option<f64>,
+    pub ttfb_ms: f64,
+    pub download_ms: f64,
+    pub total_ms: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub redirects: u32,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl HttpTiming {
+    pub fn add_hop(&mut self, hop: &HttpTiming) {
+        self.dns_ms += hop.dns_ms;
+        self.connect_ms += hop.connect_ms;
+        self.tls_ms = match (self.tls_ms, hop.tls_ms) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or_default() + b.unwrap_or_default()),
+        };
+        self.ttfb_ms += hop.ttfb_ms;
+        self.download_ms += hop.download_ms;
+        self.total_ms += hop.total_ms;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct HealthResult {
@@ -19,6 +88,15 @@ pub struct HealthResult {
     pub latency_ms: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping: Option<PingStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<HttpTiming>,
+    // What the server reported about itself, such as a protocol version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     pub checked_at: String,
 }
 
@@ -29,6 +107,24 @@ impl HealthResult {
             status: HealthStatus::Online,
             latency_ms: Some(latency_ms),
             error: None,
+            ping: None,
+            tls: None,
+            timing: None,
+            message: None,
+            checked_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn degraded(connection_id: Uuid, latency_ms: u64, reason: String) -> Self {
+        Self {
+            connection_id,
+            status: HealthStatus::Degraded,
+            latency_ms: Some(latency_ms),
+            error: Some(reason),
+            ping: None,
+            tls: None,
+            timing: None,
+            message: None,
             checked_at: chrono::Utc::now().to_rfc3339(),
         }
     }
@@ -39,6 +135,10 @@ impl HealthResult {
             status: HealthStatus::Offline,
             latency_ms: None,
             error,
+            ping: None,
+            tls: None,
+            timing: None,
+            message: None,
             checked_at: chrono::Utc::now().to_rfc3339(),
         }
     }
@@ -49,7 +149,50 @@ impl HealthResult {
             status: HealthStatus::Unknown,
             latency_ms: None,
             error: None,
+            ping: None,
+            tls: None,
+            timing: None,
+            message: None,
             checked_at: chrono::Utc::now().to_rfc3339(),
         }
     }
+
+    // Online becomes degraded. A result that is already degraded or offline
+    // keeps its status and lists the reason after its own.
+    pub fn degrade(mut self, reason: String) -> Self {
+        match self.status {
+            HealthStatus::Online => {
+                self.status = HealthStatus::Degraded;
+                self.error = Some(reason);
+            }
+            HealthStatus::Degraded | HealthStatus::Offline => {
+                self.error = Some(match self.error.take() {
+                    Some(error) => format!("{}; {}", error, reason),
+                    None => reason,
+                });
+            }
+            HealthStatus::Unknown => {}
+        }
+        self
+    }
+
+    pub fn with_ping(mut self, stats: PingStats) -> Self {
+        self.ping = Some(stats);
+        self
+    }
+
+    pub fn with_tls(mut self, info: TlsInfo) -> Self {
+        self.tls = Some(info);
+        self
+    }
+
+    pub fn with_timing(mut self, timing: HttpTiming) -> Self {
+        self.timing = Some(timing);
+        self
+    }
+
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
 }
@@ -0,0 +1,86 @@
+use crate::models::{HealthResult, HealthStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub connection_id: Uuid,
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    pub checked_at: DateTime<Utc>,
+    #[serde(default = "single_sample", skip_serializing_if = "is_single_sample")]
+    pub samples: u32,
+}
+
+fn single_sample() -> u32 {
+    1
+}
+
+fn is_single_sample(samples: &u32) -> bool {
+    *samples == 1
+}
+
+impl From<&HealthResult> for HistoryEntry {
+    fn from(result: &HealthResult) -> Self {
+        let checked_at = DateTime::parse_from_rfc3339(&result.checked_at)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+
+        Self {
+            connection_id: result.connection_id,
+            status: result.status.clone(),
+            latency_ms: result.latency_ms,
+            checked_at,
+            samples: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum HistoryWindow {
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+}
+
+impl HistoryWindow {
+    pub const ALL: [HistoryWindow; 3] = [Self::Day, Self::Week, Self::Month];
+
+    pub fn duration(self) -> chrono::Duration {
+        match self {
+            Self::Day => chrono::Duration::hours(24),
+            Self::Week => chrono::Duration::days(7),
+            Self::Month => chrono::Duration::days(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStats {
+    pub window: HistoryWindow,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_percent: Option<f64>,
+    pub samples: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p50_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p95_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p99_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Outage {
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<DateTime<Utc>>,
+    pub duration_secs: i64,
+}
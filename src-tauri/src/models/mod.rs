@@ -1,5 +1,13 @@
+pub mod alert;
+pub mod config;
 pub mod connection;
+pub mod group;
 pub mod health;
+pub mod history;
 
+pub use alert::*;
+pub use config::*;
 pub use connection::*;
+pub use group::*;
 pub use health::*;
+pub use history::*;
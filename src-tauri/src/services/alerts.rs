@@ -0,0 +1,288 @@
+use crate::models::{AlertChannel, AlertDelivery, AlertEvent, HealthStatus};
+use chrono::Utc;
+use regex::{Captures, Regex};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, StatusCode};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+const MAX_DELIVERIES: usize = 100;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            initial_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+// Sends status changes to the configured webhooks and keeps the most recent
+// deliveries in memory so the UI can show what happened.
+#[derive(Clone, Default)]
+pub struct AlertDispatcher {
+    log: Arc<Mutex<VecDeque<AlertDelivery>>>,
+    retry: RetryPolicy,
+}
+
+impl AlertDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Newest first.
+    pub fn deliveries(&self) -> Vec<AlertDelivery> {
+        self.log.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn dispatch(&self, channels: &[AlertChannel], event: AlertEvent) {
+        for channel in channels.iter().filter(|c| c.enabled) {
+            let channel = channel.clone();
+            let dispatcher = self.clone();
+            let event = event.clone();
+            tauri::async_runtime::spawn(async move {
+                dispatcher.send(&channel, &event, false).await;
+            });
+        }
+    }
+
+    pub async fn send(
+        &self,
+        channel: &AlertChannel,
+        event: &AlertEvent,
+        test: bool,
+    ) -> AlertDelivery {
+        let delivery = deliver(channel, event, self.retry, test).await;
+
+        let mut log = self.log.lock().unwrap();
+        log.push_front(delivery.clone());
+        log.truncate(MAX_DELIVERIES);
+        delivery
+    }
+}
+
+pub async fn deliver(
+    channel: &AlertChannel,
+    event: &AlertEvent,
+    retry: RetryPolicy,
+    test: bool,
+) -> AlertDelivery {
+    let mut delivery = AlertDelivery {
+        channel_id: channel.id,
+        channel_name: channel.name.clone(),
+        connection_id: event.connection_id,
+        attempted_at: Utc::now(),
+        attempts: 0,
+        delivered: false,
+        status_code: None,
+        error: None,
+        test,
+    };
+
+    let method = match Method::from_bytes(channel.method.trim().to_uppercase().as_bytes()) {
+        Ok(method) => method,
+        Err(_) => {
+            delivery.error = Some(format!("Invalid method \"{}\"", channel.method));
+            return delivery;
+        }
+    };
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            delivery.error = Some(e.to_string());
+            return delivery;
+        }
+    };
+    let body = render_template(&channel.body_template, event);
+    let has_content_type = channel
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+
+    let mut backoff = retry.initial_backoff;
+    loop {
+        delivery.attempts += 1;
+
+        let mut request = client
+            .request(method.clone(), &channel.url)
+            .body(body.clone());
+        if !has_content_type {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        for (name, value) in &channel.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let retryable = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                delivery.delivered = true;
+                delivery.status_code = Some(response.status().as_u16());
+                delivery.error = None;
+                return delivery;
+            }
+            Ok(response) => {
+                let status = response.status();
+                delivery.status_code = Some(status.as_u16());
+                delivery.error = Some(format!("HTTP {}", status));
+                status.is_server_error()
+                    || status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::REQUEST_TIMEOUT
+            }
+            Err(e) => {
+                delivery.status_code = None;
+                delivery.error = Some(e.to_string());
+                !e.is_builder()
+            }
+        };
+
+        if !retryable || delivery.attempts >= retry.attempts {
+            return delivery;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+// Replaces `{{placeholder}}` with the event's value escaped for use inside a
+// JSON string. Unknown placeholders are left as they are.
+pub fn render_template(template: &str, event: &AlertEvent) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder = PLACEHOLDER.get_or_init(|| Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap());
+
+    placeholder
+        .replace_all(template, |caps: &Captures| {
+            let value = match &caps[1] {
+                "id" | "connection_id" => event.connection_id.map(|id| id.to_string()),
+                "name" => Some(event.name.clone()),
+                "status" => Some(status_label(&event.status).into()),
+                "previous_status" => event
+                    .previous_status
+                    .as_ref()
+                    .map(|s| status_label(s).into()),
+                "latency" | "latency_ms" => event.latency_ms.map(|ms| ms.to_string()),
+                "error" => event.error.clone(),
+                "checked_at" => Some(event.checked_at.clone()),
+                _ => return caps[0].to_string(),
+            };
+            json_escape(&value.unwrap_or_default())
+        })
+        .into_owned()
+}
+
+fn status_label(status: &HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Online => "online",
+        HealthStatus::Offline => "offline",
+        HealthStatus::Degraded => "degraded",
+        HealthStatus::Unknown => "unknown",
+    }
+}
+
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    fn event() -> AlertEvent {
+        AlertEvent {
+            connection_id: Some(Uuid::new_v4()),
+            name: "NAS \"main\"".into(),
+            status: HealthStatus::Offline,
+            previous_status: Some(HealthStatus::Online),
+            latency_ms: None,
+            error: Some("Connection refused".into()),
+            checked_at: "2026-03-01T12:00:00Z".into(),
+        }
+    }
+
+    #[test]
+    fn test_render_template_escapes_values() {
+        // arrange
+        let template =
+            r#"{"text": "{{name}} is {{ status }} ({{error}}), latency {{latency}} {{unknown}}"}"#;
+
+        // act
+        let body = render_template(template, &event());
+
+        // assert
+        let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            value["text"],
+            r#"NAS "main" is offline (Connection refused), latency  {{unknown}}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_retries_server_errors() {
+        // arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (requests, mut received) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                while !request.ends_with(b"}") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let _ = requests.send(String::from_utf8_lossy(&request).into_owned());
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        let channel = AlertChannel {
+            id: Uuid::new_v4(),
+            name: "relay".into(),
+            enabled: true,
+            url: format!("http://127.0.0.1:{}/hook", port),
+            method: "put".into(),
+            headers: BTreeMap::from([("X-Token".into(), "abc".into())]),
+            body_template: r#"{"status": "{{status}}"}"#.into(),
+        };
+        let retry = RetryPolicy {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+        };
+
+        // act
+        let delivery = deliver(&channel, &event(), retry, false).await;
+
+        // assert
+        assert!(delivery.delivered);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status_code, Some(200));
+        let request = received.recv().await.unwrap().to_lowercase();
+        assert!(request.starts_with("put /hook"));
+        assert!(request.contains("x-token: abc"));
+        assert!(request.contains("content-type: application/json"));
+        assert!(request.ends_with(r#"{"status": "offline"}"#));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::models::{Connection, HealthResult};
+use crate::services::check_connection_health;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+pub const HEALTH_CHECKED_EVENT: &str = "health-checked";
+
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 8;
+pub const DEFAULT_BATCH_DEADLINE_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    pub concurrency: usize,
+    pub deadline: Duration,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_BATCH_CONCURRENCY,
+            deadline: Duration::from_secs(DEFAULT_BATCH_DEADLINE_SECS),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HealthBatches {
+    current: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl HealthBatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replacing the stored sender drops the previous one, which cancels the
+    // batch that is still holding its receiver.
+    pub fn begin(&self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        *self.current.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    pub fn cancel(&self) {
+        self.current.lock().unwrap().take();
+    }
+}
+
+pub async fn check_connections_batch<F>(
+    connections: Vec<Connection>,
+    options: BatchOptions,
+    mut cancelled: oneshot::Receiver<()>,
+    mut on_result: F,
+) -> Vec<HealthResult>
+where
+    F: FnMut(&HealthResult),
+{
+    let ids: Vec<_> = connections.iter().map(|c| c.id).collect();
+    let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for connection in connections {
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            Some(check_connection_health(&connection).await)
+        });
+    }
+
+    let deadline = sleep(options.deadline);
+    tokio::pin!(deadline);
+
+    let mut results = HashMap::new();
+    let mut interrupted = None;
+
+    loop {
+        tokio::select! {
+            joined = tasks.join_next() => match joined {
+                Some(Ok(Some(result))) => {
+                    on_result(&result);
+                    results.insert(result.connection_id, result);
+                }
+                Some(_) => {}
+                None => break,
+            },
+            _ = &mut deadline => {
+                interrupted = Some("Deadline exceeded");
+                break;
+            }
+            _ = &mut cancelled => {
+                interrupted = Some("Cancelled");
+                break;
+            }
+        }
+    }
+
+    tasks.abort_all();
+
+    ids.into_iter()
+        .map(|id| {
+            results.remove(&id).unwrap_or_else(|| {
+                let mut result = HealthResult::unknown(id);
+                result.error = interrupted.map(String::from);
+                result
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ConnectionConfig, HealthStatus};
+    use tokio::net::TcpListener;
+
+    fn ssh_connection(port: u16) -> Connection {
+        Connection::new(
+            "local".into(),
+            ConnectionConfig::Ssh {
+                host: "127.0.0.1".into(),
+                port,
+                username: "root".into(),
+                wol_enabled: false,
+                mac_address: None,
+                broadcast_addr: None,
+            },
+            0,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_batch_streams_every_result() {
+        // arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections: Vec<_> = (0..5).map(|_| ssh_connection(port)).collect();
+        let options = BatchOptions {
+            concurrency: 2,
+            ..Default::default()
+        };
+        let batches = HealthBatches::new();
+        let mut streamed = 0;
+
+        // act
+        let results =
+            check_connections_batch(connections, options, batches.begin(), |_| streamed += 1).await;
+
+        // assert
+        assert_eq!(streamed, 5);
+        assert!(results.iter().all(|r| r.status == HealthStatus::Online));
+    }
+
+    #[tokio::test]
+    async fn test_batch_deadline_marks_pending_unknown() {
+        // arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connection = Connection::new(
+            "silent".into(),
+            ConnectionConfig::Website {
+                url: format!("http://127.0.0.1:{}", port),
+                check_path: None,
+                strict_tls: false,
+                check: None,
+                auth: None,
+                auth_secret: None,
+            },
+            0,
+        );
+        let options = BatchOptions {
+            concurrency: 1,
+            deadline: Duration::from_millis(100),
+        };
+        let batches = HealthBatches::new();
+
+        // act
+        let results =
+            check_connections_batch(vec![connection], options, batches.begin(), |_| {}).await;
+
+        // assert
+        assert_eq!(results[0].status, HealthStatus::Unknown);
+        assert_eq!(results[0].error.as_deref(), Some("Deadline exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_begin_cancels_previous_batch() {
+        // arrange
+        let batches = HealthBatches::new();
+        let first = batches.begin();
+
+        // act
+        let _second = batches.begin();
+
+        // assert
+        assert!(first.await.is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use crate::models::{HealthStats, HealthStatus, HistoryEntry, HistoryWindow, Outage};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+// A result stops counting towards uptime after this long, so time the app was
+// closed is neither "up" nor "down".
+const MAX_SAMPLE_SPAN_MINS: i64 = 15;
+const DOWNSAMPLE_BUCKET_MINS: i64 = 10;
+
+pub fn window_stats(
+    entries: &[HistoryEntry],
+    window: HistoryWindow,
+    now: DateTime<Utc>,
+) -> HealthStats {
+    let from = now - window.duration();
+    let in_window: Vec<&HistoryEntry> = entries.iter().filter(|e| e.checked_at >= from).collect();
+
+    let mut latencies: Vec<(u64, u32)> = in_window
+        .iter()
+        .filter_map(|e| e.latency_ms.map(|latency| (latency, e.samples)))
+        .collect();
+    latencies.sort_unstable();
+
+    HealthStats {
+        window,
+        uptime_percent: uptime_percent(entries, from, now),
+        samples: in_window.iter().map(|e| e.samples).sum(),
+        p50_ms: percentile(&latencies, 50.0),
+        p95_ms: percentile(&latencies, 95.0),
+        p99_ms: percentile(&latencies, 99.0),
+    }
+}
+
+fn uptime_percent(
+    entries: &[HistoryEntry],
+    from: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let max_span = Duration::minutes(MAX_SAMPLE_SPAN_MINS);
+    let mut up = 0;
+    let mut observed = 0;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.status == HealthStatus::Unknown {
+            continue;
+        }
+
+        let next = entries.get(i + 1).map_or(now, |n| n.checked_at);
+        let start = entry.checked_at.max(from);
+        let end = next.min(entry.checked_at + max_span).min(now);
+        if end <= start {
+            continue;
+        }
+
+        let span = (end - start).num_milliseconds();
+        observed += span;
+        if matches!(entry.status, HealthStatus::Online | HealthStatus::Degraded) {
+            up += span;
+        }
+    }
+
+    (observed > 0).then(|| up as f64 * 100.0 / observed as f64)
+}
+
+fn percentile(sorted: &[(u64, u32)], p: f64) -> Option<u64> {
+    let total: u64 = sorted.iter().map(|(_, weight)| u64::from(*weight)).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let rank = ((p / 100.0) * total as f64).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    for (latency, weight) in sorted {
+        seen += u64::from(*weight);
+        if seen >= rank {
+            return Some(*latency);
+        }
+    }
+
+    sorted.last().map(|(latency, _)| *latency)
+}
+
+pub fn find_outages(
+    entries: &[HistoryEntry],
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<Outage> {
+    let mut outages = Vec::new();
+    let mut started_at: Option<DateTime<Utc>> = None;
+
+    for entry in entries {
+        match (entry.status.clone(), started_at) {
+            (HealthStatus::Offline, None) => started_at = Some(entry.checked_at),
+            (HealthStatus::Online | HealthStatus::Degraded, Some(start)) => {
+                outages.push(Outage {
+                    started_at: start,
+                    ended_at: Some(entry.checked_at),
+                    duration_secs: (entry.checked_at - start).num_seconds(),
+                });
+                started_at = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = started_at {
+        outages.push(Outage {
+            started_at: start,
+            ended_at: None,
+            duration_secs: (now - start).num_seconds(),
+        });
+    }
+
+    outages.retain(|o| o.ended_at.is_none_or(|end| end >= since));
+    outages
+}
+
+// Collapses entries into one per status run within each bucket, so status
+// transitions survive but a steady host costs one line per bucket.
+pub fn downsample(entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+    let bucket_secs = DOWNSAMPLE_BUCKET_MINS * 60;
+    let mut buckets: BTreeMap<(Uuid, i64), Vec<HistoryEntry>> = BTreeMap::new();
+
+    for entry in entries {
+        let bucket = entry.checked_at.timestamp().div_euclid(bucket_secs);
+        buckets
+            .entry((entry.connection_id, bucket))
+            .or_default()
+            .push(entry);
+    }
+
+    let mut result = Vec::new();
+    for (_, mut bucket) in buckets {
+        bucket.sort_by_key(|e| e.checked_at);
+
+        let mut run: Vec<HistoryEntry> = Vec::new();
+        for entry in bucket {
+            if run
+                .first()
+                .is_some_and(|first| first.status != entry.status)
+            {
+                result.push(collapse_run(std::mem::take(&mut run)));
+            }
+            run.push(entry);
+        }
+        if !run.is_empty() {
+            result.push(collapse_run(run));
+        }
+    }
+
+    result.sort_by_key(|e| e.checked_at);
+    result
+}
+
+fn collapse_run(run: Vec<HistoryEntry>) -> HistoryEntry {
+    let mut latencies: Vec<u64> = run.iter().filter_map(|e| e.latency_ms).collect();
+    latencies.sort_unstable();
+
+    let samples = run.iter().map(|e| e.samples).sum();
+    let first = run.into_iter().next().expect("run is never empty");
+
+    HistoryEntry {
+        latency_ms: latencies.get(latencies.len() / 2).copied(),
+        samples,
+        ..first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: Uuid, minutes_ago: i64, status: HealthStatus, now: DateTime<Utc>) -> HistoryEntry {
+        HistoryEntry {
+            connection_id: id,
+            status,
+            latency_ms: Some(10),
+            checked_at: now - Duration::minutes(minutes_ago),
+            samples: 1,
+        }
+    }
+
+    #[test]
+    fn test_uptime_and_outages() {
+        // arrange
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let entries = vec![
+            entry(id, 40, HealthStatus::Online, now),
+            entry(id, 30, HealthStatus::Offline, now),
+            entry(id, 20, HealthStatus::Online, now),
+            entry(id, 10, HealthStatus::Online, now),
+        ];
+
+        // act
+        let stats = window_stats(&entries, HistoryWindow::Day, now);
+        let outages = find_outages(&entries, now - Duration::hours(1), now);
+
+        // assert
+        assert_eq!(stats.uptime_percent, Some(75.0));
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].duration_secs, 600);
+    }
+
+    #[test]
+    fn test_percentiles_are_weighted() {
+        // arrange
+        let latencies = [(10, 98), (500, 2)];
+
+        // act
+        let p50 = percentile(&latencies, 50.0);
+        let p99 = percentile(&latencies, 99.0);
+
+        // assert
+        assert_eq!(p50, Some(10));
+        assert_eq!(p99, Some(500));
+    }
+
+    #[test]
+    fn test_downsample_keeps_transitions() {
+        // arrange
+        let now = DateTime::parse_from_rfc3339("2026-01-01T12:09:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let id = Uuid::new_v4();
+        let entries = vec![
+            entry(id, 8, HealthStatus::Online, now),
+            entry(id, 7, HealthStatus::Online, now),
+            entry(id, 6, HealthStatus::Offline, now),
+            entry(id, 5, HealthStatus::Online, now),
+            entry(id, 4, HealthStatus::Online, now),
+        ];
+
+        // act
+        let result = downsample(entries);
+
+        // assert
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].samples, 2);
+        assert_eq!(result[1].status, HealthStatus::Offline);
+        assert_eq!(result.iter().map(|e| e.samples).sum::<u32>(), 5);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::models::HttpAuth;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::AUTHORIZATION;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("No {0} stored for this connection")]
+    MissingSecret(&'static str),
+    #[error("Could not load client certificate: {0}")]
+    Certificate(String),
+    #[error("Invalid client private key: {0}")]
+    PrivateKey(String),
+}
+
+#[derive(Debug)]
+pub struct ClientIdentity {
+    pub certs: Vec<CertificateDer<'static>>,
+    pub key: PrivateKeyDer<'static>,
+}
+
+#[derive(Debug, Default)]
+pub struct ResolvedAuth {
+    pub header: Option<(String, String)>,
+    pub identity: Option<ClientIdentity>,
+}
+
+pub fn resolve_http_auth(auth: &HttpAuth, secret: Option<&str>) -> Result<ResolvedAuth, AuthError> {
+    match auth {
+        HttpAuth::Basic { username } => {
+            let credentials = format!("{}:{}", username, secret.unwrap_or_default());
+            Ok(ResolvedAuth {
+                header: Some((
+                    AUTHORIZATION.to_string(),
+                    format!("Basic {}", STANDARD.encode(credentials)),
+                )),
+                identity: None,
+            })
+        }
+        HttpAuth::Bearer => {
+            let token = secret.ok_or(AuthError::MissingSecret("bearer token"))?;
+            Ok(ResolvedAuth {
+                header: Some((AUTHORIZATION.to_string(), format!("Bearer {}", token))),
+                identity: None,
+            })
+        }
+        HttpAuth::Header { name } => {
+            let value = secret.ok_or(AuthError::MissingSecret("header value"))?;
+            Ok(ResolvedAuth {
+                header: Some((name.clone(), value.to_string())),
+                identity: None,
+            })
+        }
+        HttpAuth::ClientCert { cert_path } => {
+            let key_pem = secret.ok_or(AuthError::MissingSecret("private key"))?;
+            let certs = CertificateDer::pem_file_iter(cert_path)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| AuthError::Certificate(format!("{}: {}", cert_path, e)))?;
+            if certs.is_empty() {
+                return Err(AuthError::Certificate(format!(
+                    "{}: no certificates found",
+                    cert_path
+                )));
+            }
+            let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes())
+                .map_err(|e| AuthError::PrivateKey(e.to_string()))?;
+
+            Ok(ResolvedAuth {
+                header: None,
+                identity: Some(ClientIdentity { certs, key }),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_auth_header() {
+        // arrange
+        let auth = HttpAuth::Basic {
+            username: "admin".into(),
+        };
+
+        // act
+        let resolved = resolve_http_auth(&auth, Some("secret")).unwrap();
+
+        // assert
+        assert_eq!(
+            resolved.header,
+            Some(("authorization".into(), "Basic YWRtaW46c2VjcmV0".into()))
+        );
+    }
+
+    #[test]
+    fn test_bearer_requires_token() {
+        // arrange
+        let auth = HttpAuth::Bearer;
+
+        // act
+        let result = resolve_http_auth(&auth, None);
+
+        // assert
+        assert!(matches!(
+            result,
+            Err(AuthError::MissingSecret("bearer token"))
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::models::{BodyAssertion, HttpCheckSpec};
+use regex::Regex;
+use serde_json::Value;
+
+pub fn evaluate_http_assertions(spec: &HttpCheckSpec, status: u16, body: &str) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    if !spec.expected_status.is_empty() {
+        let mut matched = false;
+        for pattern in &spec.expected_status {
+            match status_matches(pattern, status) {
+                Ok(true) => matched = true,
+                Ok(false) => {}
+                Err(e) => failures.push(e),
+            }
+        }
+        if !matched {
+            failures.push(format!(
+                "Expected status {}, got {}",
+                spec.expected_status.join(", "),
+                status
+            ));
+        }
+    }
+
+    if let Some(assertion) = &spec.body_contains {
+        match body_matches(assertion, body) {
+            Ok(true) => {}
+            Ok(false) => failures.push(format!("Body does not contain \"{}\"", assertion.pattern)),
+            Err(e) => failures.push(e),
+        }
+    }
+
+    if let Some(assertion) = &spec.body_excludes {
+        match body_matches(assertion, body) {
+            Ok(false) => {}
+            Ok(true) => failures.push(format!("Body contains \"{}\"", assertion.pattern)),
+            Err(e) => failures.push(e),
+        }
+    }
+
+    if let Some(assertion) = &spec.json {
+        match serde_json::from_str::<Value>(body) {
+            Ok(json) => match json_path_lookup(&json, &assertion.path) {
+                Some(actual) if *actual == assertion.equals => {}
+                Some(actual) => failures.push(format!(
+                    "{} is {}, expected {}",
+                    assertion.path, actual, assertion.equals
+                )),
+                None => failures.push(format!("{} not found in response", assertion.path)),
+            },
+            Err(e) => failures.push(format!("Response is not JSON: {}", e)),
+        }
+    }
+
+    failures
+}
+
+fn status_matches(pattern: &str, status: u16) -> Result<bool, String> {
+    let pattern = pattern.trim();
+    let invalid = || format!("Invalid status pattern \"{}\"", pattern);
+
+    if let Some(class) = pattern
+        .strip_suffix("xx")
+        .or_else(|| pattern.strip_suffix("XX"))
+    {
+        let class: u16 = class.parse().map_err(|_| invalid())?;
+        return Ok(status / 100 == class);
+    }
+
+    if let Some((low, high)) = pattern.split_once('-') {
+        let low: u16 = low.trim().parse().map_err(|_| invalid())?;
+        let high: u16 = high.trim().parse().map_err(|_| invalid())?;
+        return Ok((low..=high).contains(&status));
+    }
+
+    let exact: u16 = pattern.parse().map_err(|_| invalid())?;
+    Ok(status == exact)
+}
+
+fn body_matches(assertion: &BodyAssertion, body: &str) -> Result<bool, String> {
+    if assertion.regex {
+        Regex::new(&assertion.pattern)
+            .map(|re| re.is_match(body))
+            .map_err(|e| format!("Invalid body pattern: {}", e))
+    } else {
+        Ok(body.contains(&assertion.pattern))
+    }
+}
+
+// Supports the dotted subset of JSONPath people actually write for health
+// endpoints: `$.status`, `checks.db.state`, `items[0].name`.
+fn json_path_lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim().trim_start_matches('$').trim_start_matches('.');
+    let mut current = value;
+
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (key, indices) = match segment.find('[') {
+            Some(i) => segment.split_at(i),
+            None => (segment, ""),
+        };
+
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+
+        for index in indices.split('[').skip(1) {
+            let index: usize = index.trim_end_matches(']').parse().ok()?;
+            current = current.get(index)?;
+        }
+    }
+
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{JsonAssertion, StatusPolicy};
+    use serde_json::json;
+
+    fn spec() -> HttpCheckSpec {
+        HttpCheckSpec {
+            method: None,
+            headers: Default::default(),
+            body: None,
+            expected_status: Vec::new(),
+            body_contains: None,
+            body_excludes: None,
+            json: None,
+            on_failure: StatusPolicy::Offline,
+        }
+    }
+
+    #[test]
+    fn test_status_patterns() {
+        // arrange
+        let patterns = ["204", "200-299", "3xx"];
+
+        // act
+        let results: Vec<_> = patterns
+            .iter()
+            .map(|p| status_matches(p, 204).unwrap())
+            .collect();
+
+        // assert
+        assert_eq!(results, vec![true, true, false]);
+        assert!(status_matches("abc", 200).is_err());
+    }
+
+    #[test]
+    fn test_json_path_lookup() {
+        // arrange
+        let value = json!({ "checks": [{ "name": "db", "state": "ok" }] });
+
+        // act
+        let found = json_path_lookup(&value, "$.checks[0].state");
+        let missing = json_path_lookup(&value, "checks[3].state");
+
+        // assert
+        assert_eq!(found, Some(&json!("ok")));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_json_assertion_reports_degraded_payload() {
+        // arrange
+        let spec = HttpCheckSpec {
+            json: Some(JsonAssertion {
+                path: "$.status".into(),
+                equals: json!("ok"),
+            }),
+            ..spec()
+        };
+
+        // act
+        let failures = evaluate_http_assertions(&spec, 200, r#"{"status":"degraded"}"#);
+
+        // assert
+        assert_eq!(failures, vec![r#"$.status is "degraded", expected "ok""#]);
+    }
+
+    #[test]
+    fn test_body_assertions() {
+        // arrange
+        let spec = HttpCheckSpec {
+            expected_status: vec!["200".into()],
+            body_contains: Some(BodyAssertion {
+                pattern: r"version \d+".into(),
+                regex: true,
+            }),
+            body_excludes: Some(BodyAssertion {
+                pattern: "maintenance".into(),
+                regex: false,
+            }),
+            ..spec()
+        };
+
+        // act
+        let passing = evaluate_http_assertions(&spec, 200, "running version 42");
+        let failing = evaluate_http_assertions(&spec, 503, "down for maintenance");
+
+        // assert
+        assert!(passing.is_empty());
+        assert_eq!(failing.len(), 3);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::models::{HttpTiming, TlsInfo};
+use crate::services::{probe_tls_config, ClientIdentity, TlsError, TlsRecorder};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{LOCATION, USER_AGENT};
+use reqwest::redirect::Policy;
+use reqwest::{Method, StatusCode, Url};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tower::util::MapResponseLayer;
+
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ProbeError {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("Connection failed: {0}")]
+    Connect(String),
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+    #[error("Certificate invalid: {0}")]
+    CertificateRejected(String),
+    #[error("HTTP error: {0}")]
+    Http(String),
+    #[error("Too many redirects")]
+    TooManyRedirects,
+}
+
+impl From<reqwest::Error> for ProbeError {
+    // reqwest's own message only names the step that failed; the cause (a
+    // refused connection, a failed lookup) is at the end of the source chain.
+    fn from(error: reqwest::Error) -> Self {
+        let mut cause: &dyn std::error::Error = &error;
+        while let Some(source) = cause.source() {
+            cause = source;
+        }
+        if error.is_connect() {
+            ProbeError::Connect(cause.to_string())
+        } else {
+            ProbeError::Http(cause.to_string())
+        }
+    }
+}
+
+pub struct ProbeRequest<'a> {
+    pub method: Method,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<&'a str>,
+    pub read_body: bool,
+    pub strict_tls: bool,
+    pub identity: Option<&'a ClientIdentity>,
+}
+
+pub struct ProbeResponse {
+    pub status: StatusCode,
+    pub location: Option<String>,
+    pub body: String,
+    pub timing: HttpTiming,
+    pub tls: Option<TlsInfo>,
+}
+
+// When each connection phase finished, as seen by the hooks below.
+#[derive(Default)]
+struct Phases {
+    resolved: OnceLock<Instant>,
+    connected: OnceLock<Instant>,
+}
+
+// Resolves like the system would, returning every address so the connector
+// can fall back from one to the next, and notes when the lookup finished.
+struct TimedResolver(Arc<Phases>);
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let phases = self.0.clone();
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            let _ = phases.resolved.set(Instant::now());
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Sends one request through a fresh reqwest client, which honours the
+// system proxy settings (HTTP_PROXY, HTTPS_PROXY and NO_PROXY included) and
+// negotiates HTTP/2 and compression as usual. The resolver, the TLS recorder
+// and a layer around the connector mark when each phase (DNS, TCP, TLS,
+// waiting, download) ended, so they can be timed on the same connection.
+// Redirects are left to the caller.
+pub async fn send_probe(
+    url: &Url,
+    request: &ProbeRequest<'_>,
+) -> Result<ProbeResponse, ProbeError> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| ProbeError::InvalidUrl(url.to_string()))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let https = match url.scheme() {
+        "https" => true,
+        "http" => false,
+        other => {
+            return Err(ProbeError::InvalidUrl(format!(
+                "unsupported scheme {}",
+                other
+            )))
+        }
+    };
+
+    let phases = Arc::new(Phases::default());
+    let connected = phases.clone();
+    let mut builder = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .dns_resolver(Arc::new(TimedResolver(phases.clone())))
+        .connector_layer(MapResponseLayer::new(move |conn| {
+            let _ = connected.connected.set(Instant::now());
+            conn
+        }));
+    let mut recorder: Option<Arc<TlsRecorder>> = None;
+    if https {
+        let (config, tls) = probe_tls_config(host, request.strict_tls, request.identity)?;
+        builder = builder.use_preconfigured_tls(config);
+        recorder = Some(tls);
+    }
+    let client = builder.build()?;
+
+    let mut http_request = client.request(request.method.clone(), url.clone()).header(
+        USER_AGENT,
+        concat!("turbo-waffle/", env!("CARGO_PKG_VERSION")),
+    );
+    for (name, value) in &request.headers {
+        http_request = http_request.header(name.as_str(), value.as_str());
+    }
+    if let Some(body) = request.body {
+        http_request = http_request.body(body.to_string());
+    }
+
+    let start = Instant::now();
+    let mut response = match http_request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let rejected = recorder.as_ref().and_then(|tls| tls.validation_error());
+            return Err(match rejected {
+                Some(error) => ProbeError::CertificateRejected(error),
+                None => e.into(),
+            });
+        }
+    };
+    let first_byte = start.elapsed();
+
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+
+    let mut body = Vec::new();
+    if request.read_body {
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+    }
+    let finished = start.elapsed();
+
+    // A host given as an address is never looked up, so it has no DNS phase.
+    let since_start = |at: Option<Instant>| at.map(|at| at.duration_since(start));
+    let resolved = since_start(phases.resolved.get().copied()).unwrap_or_default();
+    let connected = since_start(phases.connected.get().copied()).unwrap_or(resolved);
+    let tls_started =
+        since_start(recorder.as_ref().and_then(|tls| tls.started())).unwrap_or(connected);
+    let tls = match &recorder {
+        Some(tls) => tls.take_info()?,
+        None => None,
+    };
+
+    Ok(ProbeResponse {
+        status,
+        location,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        timing: HttpTiming {
+            dns_ms: millis(resolved),
+            connect_ms: millis(tls_started.saturating_sub(resolved)),
+            tls_ms: https.then(|| millis(connected.saturating_sub(tls_started))),
+            ttfb_ms: millis(first_byte.saturating_sub(connected)),
+            download_ms: millis(finished - first_byte),
+            total_ms: millis(finished),
+            redirects: 0,
+        },
+        tls,
+    })
+}
+
+fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 10_000.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_probe_reads_status_and_body() {
+        // arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 418 I'm a teapot\r\ncontent-length: 5\r\n\r\nshort")
+                .await
+                .unwrap();
+        });
+        let url = Url::parse(&format!("http://localhost:{}/brew", port)).unwrap();
+        let request = ProbeRequest {
+            method: Method::GET,
+            headers: BTreeMap::new(),
+            body: None,
+            read_body: true,
+            strict_tls: false,
+            identity: None,
+        };
+
+        // act
+        let response = send_probe(&url, &request).await.unwrap();
+
+        // assert
+        assert_eq!(response.status, StatusCode::IM_A_TEAPOT);
+        assert_eq!(response.body, "short");
+        assert!(response.tls.is_none());
+        assert_eq!(response.timing.tls_ms, None);
+        assert!(response.timing.total_ms >= response.timing.dns_ms);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::models::HealthResult;
+use std::collections::BTreeMap;
+use std::process::{ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+pub const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 10;
+const MAX_OUTPUT_LEN: u64 = 64 * 1024;
+const SUMMARY_LEN: usize = 200;
+// How long output is still collected after the probe exits, in case
+// something it started in the background keeps the pipe open.
+const OUTPUT_DRAIN_MS: u64 = 100;
+
+pub fn launch_command(
+    command: &str,
+    working_dir: Option<&str>,
+    env: &BTreeMap<String, String>,
+) -> std::io::Result<()> {
+    let mut launcher = std::process::Command::new("sh");
+    launcher
+        .arg("-c")
+        .arg(command)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(dir) = non_empty(working_dir) {
+        launcher.current_dir(dir);
+    }
+    // A group of its own, so signals aimed at the app leave it running.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        launcher.process_group(0);
+    }
+
+    launcher.spawn().map(|_| ())
+}
+
+// The probe decides the status itself, so latency thresholds are not applied;
+// the latency reported is how long it ran.
+pub async fn check_command_health(
+    connection_id: Uuid,
+    probe_command: &str,
+    working_dir: Option<&str>,
+    env: &BTreeMap<String, String>,
+    timeout_secs: u64,
+) -> HealthResult {
+    let mut probe = Command::new("sh");
+    probe
+        .arg("-c")
+        .arg(probe_command)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    if let Some(dir) = non_empty(working_dir) {
+        probe.current_dir(dir);
+    }
+    // Anything the probe starts joins its group, so a timeout can take down
+    // the whole tree rather than just the shell.
+    #[cfg(unix)]
+    probe.process_group(0);
+
+    let start = Instant::now();
+    let mut child = match probe.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return HealthResult::offline(
+                connection_id,
+                Some(format!("Failed to run probe: {}", e)),
+            )
+        }
+    };
+    // Declared after `child`, so it runs first on every way out of here,
+    // including a cancelled check dropping this future.
+    let mut group = ProcessGroup(child.id());
+    let Some(mut stdout) = child.stdout.take() else {
+        return HealthResult::offline(connection_id, Some("Probe has no output".into()));
+    };
+
+    let run = async {
+        let mut output = Vec::new();
+        {
+            // Keep draining past the limit so a chatty probe never blocks on
+            // a full pipe.
+            let read = async {
+                (&mut stdout)
+                    .take(MAX_OUTPUT_LEN)
+                    .read_to_end(&mut output)
+                    .await?;
+                tokio::io::copy(&mut stdout, &mut tokio::io::sink()).await
+            };
+            tokio::pin!(read);
+            let mut read_done = false;
+            tokio::select! {
+                exited = exited(&mut child) => exited?,
+                read = &mut read => {
+                    read?;
+                    read_done = true;
+                    exited(&mut child).await?
+                }
+            };
+            // The exit status is what counts; don't wait on a background
+            // process that inherited stdout.
+            if !read_done {
+                let _ = timeout(Duration::from_millis(OUTPUT_DRAIN_MS), &mut read).await;
+            }
+        }
+
+        // The probe has exited but is not reaped yet, so its group id can't
+        // have been handed out again. Whatever it left running goes now, and
+        // nothing is killed once the pid is free.
+        group.kill();
+        let status = child.wait().await?;
+        Ok::<_, std::io::Error>((status, output))
+    };
+
+    match timeout(Duration::from_secs(timeout_secs.max(1)), run).await {
+        Ok(Ok((status, output))) => {
+            let latency = start.elapsed().as_millis() as u64;
+            probe_result(connection_id, status, latency, &output)
+        }
+        Ok(Err(e)) => HealthResult::offline(connection_id, Some(e.to_string())),
+        Err(_) => HealthResult::offline(
+            connection_id,
+            Some(format!("Probe timed out after {} s", timeout_secs.max(1))),
+        ),
+    }
+}
+
+// Waits for the probe to exit without reaping it, which `Child::wait` would
+// do, so its pid stays reserved until the caller is done with its group.
+#[cfg(unix)]
+async fn exited(child: &mut tokio::process::Child) -> std::io::Result<()> {
+    let Some(pid) = child.id() else {
+        return Ok(());
+    };
+    tokio::task::spawn_blocking(move || loop {
+        // SAFETY: `info` is plain C data that waitid fills in; WNOWAIT
+        // leaves the child to be reaped by `Child::wait`.
+        let waited = unsafe {
+            let mut info: libc::siginfo_t = std::mem::zeroed();
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if waited == 0 {
+            return Ok(());
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    })
+    .await?
+}
+
+#[cfg(not(unix))]
+async fn exited(child: &mut tokio::process::Child) -> std::io::Result<()> {
+    child.wait().await.map(|_| ())
+}
+
+// Kills whatever is left of the probe's process group, when asked or when
+// dropped; `kill_on_drop` alone only reaches the shell.
+struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    // Only called while the probe is unreaped, after which the group id could
+    // belong to someone else, so it is forgotten.
+    fn kill(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0.take() {
+            // SAFETY: killpg has no memory-safety preconditions. The group id
+            // is the probe's pid since it was spawned with `process_group(0)`,
+            // and it stays reserved while the probe is unreaped or any member
+            // is alive.
+            unsafe {
+                libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+fn probe_result(
+    connection_id: Uuid,
+    status: ExitStatus,
+    latency_ms: u64,
+    output: &[u8],
+) -> HealthResult {
+    let message = first_line(output);
+    match status.code() {
+        Some(0) => {
+            let result = HealthResult::online(connection_id, latency_ms);
+            match message {
+                Some(message) => result.with_message(message),
+                None => result,
+            }
+        }
+        Some(1) => HealthResult::degraded(
+            connection_id,
+            latency_ms,
+            message.unwrap_or_else(|| "Probe reported a warning".into()),
+        ),
+        Some(2) => HealthResult::offline(
+            connection_id,
+            Some(message.unwrap_or_else(|| "Probe reported a failure".into())),
+        ),
+        Some(code) => HealthResult::offline(
+            connection_id,
+            Some(match message {
+                Some(message) => format!("Probe exited with status {}: {}", code, message),
+                None => format!("Probe exited with status {}", code),
+            }),
+        ),
+        None => HealthResult::offline(connection_id, Some("Probe was killed by a signal".into())),
+    }
+}
+
+// Nagios plugins put performance data after a `|`, which is not for people.
+fn first_line(output: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(output);
+    let line = text.lines().next()?;
+    let line = line.split('|').next().unwrap_or(line).trim();
+    (!line.is_empty()).then(|| line.chars().take(SUMMARY_LEN).collect())
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HealthStatus;
+
+    async fn run_probe(probe_command: &str) -> HealthResult {
+        check_command_health(Uuid::new_v4(), probe_command, None, &BTreeMap::new(), 5).await
+    }
+
+    #[tokio::test]
+    async fn test_probe_exit_codes() {
+        // arrange
+        let probes = [
+            "echo 'OK - 3 jobs queued|jobs=3'; exit 0",
+            "echo 'WARNING - 40 jobs queued'; exit 1",
+            "echo 'CRITICAL - worker down'; exit 2",
+            "echo 'no such queue'; exit 3",
+        ];
+
+        // act
+        let mut results = Vec::new();
+        for probe in probes {
+            results.push(run_probe(probe).await);
+        }
+
+        // assert
+        assert_eq!(results[0].status, HealthStatus::Online);
+        assert_eq!(results[0].message.as_deref(), Some("OK - 3 jobs queued"));
+        assert_eq!(results[1].status, HealthStatus::Degraded);
+        assert_eq!(
+            results[1].error.as_deref(),
+            Some("WARNING - 40 jobs queued")
+        );
+        assert_eq!(results[2].status, HealthStatus::Offline);
+        assert_eq!(results[2].error.as_deref(), Some("CRITICAL - worker down"));
+        assert_eq!(
+            results[3].error.as_deref(),
+            Some("Probe exited with status 3: no such queue")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_environment() {
+        // arrange
+        let dir = std::env::temp_dir();
+        let env = BTreeMap::from([("GREETING".to_string(), "hello".to_string())]);
+
+        // act
+        let result = check_command_health(
+            Uuid::new_v4(),
+            r#"echo "$GREETING from $(pwd)""#,
+            dir.to_str(),
+            &env,
+            5,
+        )
+        .await;
+
+        // assert
+        let expected = format!("hello from {}", dir.canonicalize().unwrap().display());
+        assert_eq!(result.message, Some(expected));
+    }
+
+    // Gone, or a zombie waiting for a parent that does not reap it.
+    #[cfg(target_os = "linux")]
+    async fn wait_until_gone(pid_file: &std::path::Path) -> bool {
+        let pid = std::fs::read_to_string(pid_file).unwrap();
+        let _ = std::fs::remove_file(pid_file);
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+                .map(|stat| {
+                    !stat
+                        .rsplit(')')
+                        .next()
+                        .unwrap_or("")
+                        .trim_start()
+                        .starts_with('Z')
+                })
+                .unwrap_or(false)
+        };
+        for _ in 0..20 {
+            if !alive() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        !alive()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn pid_file() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("probe-{}.pid", Uuid::new_v4()))
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_probe_timeout_kills_process_group() {
+        // arrange
+        let pid_file = pid_file();
+        let probe = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+
+        // act
+        let started = Instant::now();
+        let result = check_command_health(Uuid::new_v4(), &probe, None, &BTreeMap::new(), 1).await;
+
+        // assert
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(result.error.as_deref(), Some("Probe timed out after 1 s"));
+        assert!(wait_until_gone(&pid_file).await);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_probe_exit_does_not_wait_for_background_output() {
+        // arrange
+        let pid_file = pid_file();
+        let probe = format!(
+            "sleep 30 & echo $! > {}; echo up; exit 0",
+            pid_file.display()
+        );
+
+        // act
+        let started = Instant::now();
+        let result = run_probe(&probe).await;
+
+        // assert
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(result.status, HealthStatus::Online);
+        assert_eq!(result.message.as_deref(), Some("up"));
+        assert!(wait_until_gone(&pid_file).await);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cancelled_probe_kills_process_group() {
+        // arrange
+        let pid_file = pid_file();
+        let probe = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+
+        // act
+        let cancelled = timeout(Duration::from_millis(500), run_probe(&probe)).await;
+
+        // assert
+        assert!(cancelled.is_err());
+        assert!(wait_until_gone(&pid_file).await);
+    }
+}
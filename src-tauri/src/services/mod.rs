@@ -1,7 +1,37 @@
+pub mod alerts;
+pub mod batch;
+pub mod history;
+pub mod http_auth;
+pub mod http_check;
+pub mod http_probe;
+pub mod local_command;
+pub mod notifications;
 pub mod ping;
+pub mod protocol_check;
+pub mod remote_desktop;
+pub mod scheduler;
+pub mod search;
+pub mod serial;
+pub mod service_probe;
 pub mod terminal;
+pub mod tls;
 pub mod wol;
 
+pub use alerts::*;
+pub use batch::*;
+pub use history::*;
+pub use http_auth::*;
+pub use http_check::*;
+pub use http_probe::*;
+pub use local_command::*;
+pub use notifications::*;
 pub use ping::*;
+pub use protocol_check::*;
+pub use remote_desktop::*;
+pub use scheduler::*;
+pub use search::*;
+pub use serial::*;
+pub use service_probe::*;
 pub use terminal::*;
+pub use tls::*;
 pub use wol::*;
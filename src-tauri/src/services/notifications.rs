@@ -0,0 +1,292 @@
+use crate::models::{Connection, HealthResult, HealthStatus, NotificationSettings};
+use crate::services::CheckOrigin;
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transition {
+    Down { reason: Option<String> },
+    Recovered { outage_secs: i64 },
+}
+
+#[derive(Debug, Default)]
+struct OutageState {
+    failures: u32,
+    since: Option<DateTime<Utc>>,
+    notified: bool,
+    // When a reported outage ended during quiet hours, until that is shown.
+    recovered: Option<DateTime<Utc>>,
+}
+
+// Turns a stream of results into down/recovered transitions. A connection is
+// only reported down after `failure_threshold` scheduled checks in a row come
+// back offline, and only reported recovered if the user was told it was down.
+// Nothing is shown during quiet hours; an outage that outlasts them is
+// reported on the next failed check, and a recovery during them on the first
+// check after they end.
+#[derive(Debug, Default)]
+pub struct TransitionTracker {
+    states: HashMap<Uuid, OutageState>,
+}
+
+impl TransitionTracker {
+    pub fn observe(
+        &mut self,
+        result: &HealthResult,
+        origin: CheckOrigin,
+        failure_threshold: u32,
+        quiet: bool,
+    ) -> Option<Transition> {
+        let checked_at = DateTime::parse_from_rfc3339(&result.checked_at)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+
+        match result.status {
+            HealthStatus::Unknown => None,
+            // Clicking "check" a few times in a row is not a sustained outage.
+            HealthStatus::Offline if origin == CheckOrigin::Manual => None,
+            HealthStatus::Offline => {
+                let state = self.states.entry(result.connection_id).or_default();
+                // Down again before its recovery was shown, so as far as the
+                // user knows it never came back.
+                state.recovered = None;
+                state.failures += 1;
+                state.since.get_or_insert(checked_at);
+
+                if !state.notified && !quiet && state.failures >= failure_threshold.max(1) {
+                    state.notified = true;
+                    return Some(Transition::Down {
+                        reason: result.error.clone(),
+                    });
+                }
+                None
+            }
+            HealthStatus::Online | HealthStatus::Degraded => {
+                let state = self.states.get_mut(&result.connection_id)?;
+                if state.notified && quiet {
+                    state.failures = 0;
+                    state.recovered.get_or_insert(checked_at);
+                    return None;
+                }
+
+                let state = self.states.remove(&result.connection_id)?;
+                let since = state.since?;
+                let recovered = state.recovered.unwrap_or(checked_at);
+                state.notified.then(|| Transition::Recovered {
+                    outage_secs: (recovered - since).num_seconds().max(0),
+                })
+            }
+        }
+    }
+
+    pub fn forget(&mut self, id: Uuid) {
+        self.states.remove(&id);
+    }
+}
+
+pub fn in_quiet_hours(settings: &NotificationSettings, now: DateTime<Local>) -> bool {
+    settings
+        .quiet_hours
+        .is_some_and(|quiet| quiet.contains(now.time()))
+}
+
+pub fn notify_transition<R: Runtime>(
+    app: &AppHandle<R>,
+    connection: &Connection,
+    transition: &Transition,
+) {
+    let (title, body) = match transition {
+        Transition::Down { reason } => (
+            format!("{} is offline", connection.name),
+            reason.clone().unwrap_or_else(|| "No response".into()),
+        ),
+        Transition::Recovered { outage_secs } => (
+            format!("{} is back online", connection.name),
+            format!("Down for {}", format_duration(*outage_secs)),
+        ),
+    };
+
+    let _ = app.notification().builder().title(title).body(body).show();
+}
+
+pub fn format_duration(secs: i64) -> String {
+    let (days, hours, minutes, seconds) = (
+        secs / 86_400,
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+    );
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m {}s", minutes, seconds),
+        (0, _, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuietHours;
+    use chrono::{Duration, NaiveTime, TimeZone};
+
+    fn at(result: HealthResult, start: DateTime<Utc>, minutes: i64) -> HealthResult {
+        HealthResult {
+            checked_at: (start + Duration::minutes(minutes)).to_rfc3339(),
+            ..result
+        }
+    }
+
+    #[test]
+    fn test_debounces_failures_and_reports_recovery() {
+        // arrange
+        let mut tracker = TransitionTracker::default();
+        let id = Uuid::new_v4();
+        let start = Utc::now();
+        let results = [
+            at(HealthResult::online(id, 10), start, 0),
+            at(HealthResult::offline(id, Some("refused".into())), start, 1),
+            at(HealthResult::online(id, 10), start, 2),
+            at(HealthResult::offline(id, None), start, 3),
+            at(HealthResult::offline(id, Some("timeout".into())), start, 4),
+            at(HealthResult::offline(id, None), start, 5),
+            at(HealthResult::unknown(id), start, 6),
+            at(HealthResult::degraded(id, 900, "slow".into()), start, 8),
+        ];
+
+        // act
+        let transitions: Vec<_> = results
+            .iter()
+            .filter_map(|result| tracker.observe(result, CheckOrigin::Scheduled, 2, false))
+            .collect();
+
+        // assert
+        assert_eq!(
+            transitions,
+            vec![
+                Transition::Down {
+                    reason: Some("timeout".into())
+                },
+                Transition::Recovered { outage_secs: 300 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_and_manual_checks() {
+        // arrange
+        let mut tracker = TransitionTracker::default();
+        let (hidden, manual) = (Uuid::new_v4(), Uuid::new_v4());
+        let start = Utc::now();
+        let offline = |id, minutes| at(HealthResult::offline(id, None), start, minutes);
+        let online = |id, minutes| at(HealthResult::online(id, 10), start, minutes);
+        let scheduled = CheckOrigin::Scheduled;
+
+        // act
+        let quiet_down = tracker.observe(&offline(hidden, 0), scheduled, 1, true);
+        let quiet_recovery = tracker.observe(&online(hidden, 1), scheduled, 1, false);
+        let late_down = [
+            tracker.observe(&offline(hidden, 2), scheduled, 1, true),
+            tracker.observe(&offline(hidden, 3), scheduled, 1, false),
+        ];
+        let manual_down = [
+            tracker.observe(&offline(manual, 0), CheckOrigin::Manual, 2, false),
+            tracker.observe(&offline(manual, 1), CheckOrigin::Manual, 2, false),
+            tracker.observe(&offline(manual, 2), scheduled, 2, false),
+        ];
+
+        // assert
+        assert_eq!(quiet_down, None);
+        assert_eq!(quiet_recovery, None);
+        assert_eq!(late_down[0], None);
+        assert_eq!(late_down[1], Some(Transition::Down { reason: None }));
+        assert_eq!(manual_down, [None, None, None]);
+    }
+
+    #[test]
+    fn test_recovery_during_quiet_hours_is_shown_after() {
+        // arrange
+        let mut tracker = TransitionTracker::default();
+        let id = Uuid::new_v4();
+        let start = Utc::now();
+        let scheduled = CheckOrigin::Scheduled;
+
+        // act
+        let down = tracker.observe(
+            &at(HealthResult::offline(id, None), start, 0),
+            scheduled,
+            1,
+            false,
+        );
+        let quiet = [
+            tracker.observe(
+                &at(HealthResult::online(id, 10), start, 10),
+                scheduled,
+                1,
+                true,
+            ),
+            tracker.observe(
+                &at(HealthResult::online(id, 10), start, 20),
+                scheduled,
+                1,
+                true,
+            ),
+        ];
+        let after = tracker.observe(
+            &at(HealthResult::online(id, 10), start, 90),
+            scheduled,
+            1,
+            false,
+        );
+        let again = tracker.observe(
+            &at(HealthResult::online(id, 10), start, 91),
+            scheduled,
+            1,
+            false,
+        );
+
+        // assert
+        assert_eq!(down, Some(Transition::Down { reason: None }));
+        assert_eq!(quiet, [None, None]);
+        assert_eq!(after, Some(Transition::Recovered { outage_secs: 600 }));
+        assert_eq!(again, None);
+    }
+
+    #[test]
+    fn test_quiet_hours_wrap_midnight() {
+        // arrange
+        let settings = NotificationSettings {
+            quiet_hours: Some(QuietHours {
+                start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            }),
+            ..Default::default()
+        };
+        let local = |hour| Local.with_ymd_and_hms(2026, 3, 1, hour, 30, 0).unwrap();
+
+        // act
+        let late = in_quiet_hours(&settings, local(23));
+        let early = in_quiet_hours(&settings, local(6));
+        let day = in_quiet_hours(&settings, local(12));
+
+        // assert
+        assert!(late && early);
+        assert!(!day);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        // arrange
+        let durations = [45, 330, 3_900, 183_600];
+
+        // act
+        let formatted: Vec<_> = durations.iter().map(|s| format_duration(*s)).collect();
+
+        // assert
+        assert_eq!(formatted, vec!["45s", "5m 30s", "1h 5m", "2d 3h"]);
+    }
+}
@@ -1,26 +1,168 @@
-use crate::models::{Connection, ConnectionConfig, HealthResult};
-use std::net::{SocketAddr, ToSocketAddrs};
+use crate::models::{
+    CheckMode, Connection, ConnectionConfig, HealthResult, HealthThresholds, HttpAuth,
+    HttpCheckSpec, HttpTiming, PingStats, StatusPolicy,
+};
+use crate::services::{
+    apply_tls_policy, check_command_health, check_interval, check_mysql_health,
+    check_postgres_health, check_redis_health, check_serial_health, check_service_health,
+    check_vnc_health, evaluate_http_assertions, resolve_http_auth, send_probe, ProbeError,
+    ProbeRequest, ProbeResponse, ResolvedAuth, DEFAULT_PROBE_TIMEOUT_SECS,
+};
+use crate::storage::SecretStorage;
+use reqwest::{Method, StatusCode};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 use std::time::{Duration, Instant};
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
 use tokio::net::TcpStream;
-use tokio::time::timeout;
+use tokio::time::{sleep, timeout};
 
-const TIMEOUT_SECS: u64 = 5;
+pub(crate) const TIMEOUT_SECS: u64 = 5;
+const MAX_REDIRECTS: u32 = 10;
+const DEFAULT_PING_COUNT: u16 = 4;
+const PING_TIMEOUT_SECS: u64 = 1;
+const PING_INTERVAL_MS: u64 = 200;
+const PING_PAYLOAD: [u8; 56] = [0; 56];
 
 pub async fn check_connection_health(connection: &Connection) -> HealthResult {
+    if connection.check_mode == CheckMode::Icmp {
+        if let Some(host) = icmp_target(&connection.config) {
+            let count = connection.ping_count.unwrap_or(DEFAULT_PING_COUNT).max(1);
+            // `None` means ICMP sockets are not permitted here, so fall back to
+            // the regular TCP/HTTP check below.
+            if let Some(result) =
+                check_icmp_health(connection.id, &host, count, &connection.thresholds).await
+            {
+                return result;
+            }
+        }
+    }
+
     match &connection.config {
-        ConnectionConfig::Website { url, check_path } => {
-            check_http_health(connection.id, url, check_path.as_deref()).await
+        ConnectionConfig::Website {
+            url,
+            check_path,
+            strict_tls,
+            check,
+            auth,
+            ..
+        } => {
+            let auth = match auth
+                .as_ref()
+                .map(|auth| load_http_auth(connection.id, auth))
+            {
+                Some(Ok(resolved)) => resolved,
+                Some(Err(e)) => return HealthResult::offline(connection.id, Some(e)),
+                None => ResolvedAuth::default(),
+            };
+
+            check_http_health(
+                connection.id,
+                url,
+                check_path.as_deref(),
+                check.as_deref(),
+                &auth,
+                *strict_tls,
+                &connection.thresholds,
+            )
+            .await
+        }
+        ConnectionConfig::Ssh { host, port, .. } | ConnectionConfig::Rdp { host, port, .. } => {
+            check_tcp_health(connection.id, host, *port, &connection.thresholds).await
+        }
+        ConnectionConfig::Vnc { host, port, .. } => {
+            check_vnc_health(connection.id, host, *port, &connection.thresholds).await
+        }
+        ConnectionConfig::Postgres {
+            host,
+            port,
+            username,
+            database,
+        } => {
+            check_postgres_health(
+                connection.id,
+                host,
+                *port,
+                username.as_deref(),
+                database.as_deref(),
+                &connection.thresholds,
+            )
+            .await
+        }
+        ConnectionConfig::Mysql { host, port, .. } => {
+            check_mysql_health(connection.id, host, *port, &connection.thresholds).await
         }
-        ConnectionConfig::Ssh { host, port, .. } => {
-            check_tcp_health(connection.id, host, *port).await
+        ConnectionConfig::Redis { host, port, .. } => {
+            check_redis_health(connection.id, host, *port, &connection.thresholds).await
         }
+        ConnectionConfig::Service {
+            host,
+            port,
+            protocol,
+            probe,
+        } => {
+            check_service_health(
+                connection.id,
+                host,
+                *port,
+                *protocol,
+                probe.as_ref(),
+                &connection.thresholds,
+            )
+            .await
+        }
+        ConnectionConfig::Command {
+            working_dir,
+            env,
+            probe_command,
+            timeout_secs,
+            ..
+        } => match probe_command
+            .as_deref()
+            .filter(|probe| !probe.trim().is_empty())
+        {
+            Some(probe_command) => {
+                // A probe still running when the next check is due would
+                // overlap it.
+                let timeout_secs = timeout_secs
+                    .unwrap_or(DEFAULT_PROBE_TIMEOUT_SECS)
+                    .min(check_interval(connection).as_secs());
+                check_command_health(
+                    connection.id,
+                    probe_command,
+                    working_dir.as_deref(),
+                    env,
+                    timeout_secs,
+                )
+                .await
+            }
+            None => HealthResult::unknown(connection.id),
+        },
+        ConnectionConfig::Serial {
+            device,
+            open_check,
+            keep_dtr,
+            ..
+        } => check_serial_health(connection.id, device, *open_check, *keep_dtr).await,
     }
 }
 
+fn load_http_auth(connection_id: uuid::Uuid, auth: &HttpAuth) -> Result<ResolvedAuth, String> {
+    let secret = SecretStorage::new()
+        .and_then(|secrets| secrets.get(connection_id))
+        .map_err(|e| e.to_string())?;
+    resolve_http_auth(auth, secret.as_deref()).map_err(|e| e.to_string())
+}
+
 async fn check_http_health(
     connection_id: uuid::Uuid,
     base_url: &str,
     check_path: Option<&str>,
+    check: Option<&HttpCheckSpec>,
+    auth: &ResolvedAuth,
+    strict_tls: bool,
+    thresholds: &HealthThresholds,
 ) -> HealthResult {
     let url = if let Some(path) = check_path {
         format!("{}{}", base_url.trim_end_matches('/'), path)
@@ -28,55 +170,571 @@ async fn check_http_health(
         base_url.to_string()
     };
 
-    let start = Instant::now();
+    let mut url = match reqwest::Url::parse(&url) {
+        Ok(url) => url,
+        Err(e) => return HealthResult::offline(connection_id, Some(format!("Invalid URL: {}", e))),
+    };
+
+    let method = check
+        .and_then(|spec| spec.method.as_deref())
+        .unwrap_or("GET")
+        .to_uppercase();
+    let method = match Method::from_bytes(method.as_bytes()) {
+        Ok(m) => m,
+        Err(_) => {
+            return HealthResult::offline(
+                connection_id,
+                Some(format!("Invalid HTTP method {}", method)),
+            )
+        }
+    };
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(TIMEOUT_SECS))
-        .danger_accept_invalid_certs(true)
-        .build();
+    let mut headers: BTreeMap<String, String> =
+        check.map(|spec| spec.headers.clone()).unwrap_or_default();
+    if let Some((name, value)) = &auth.header {
+        headers.insert(name.clone(), value.clone());
+    }
 
-    let client = match client {
-        Ok(c) => c,
-        Err(e) => return HealthResult::offline(connection_id, Some(e.to_string())),
+    let origin = url.origin();
+    let mut probe = ProbeRequest {
+        method,
+        headers,
+        body: check.and_then(|spec| spec.body.as_deref()),
+        read_body: check.is_some_and(|spec| spec.needs_body()),
+        strict_tls,
+        identity: auth.identity.as_ref(),
     };
 
-    match client.get(&url).send().await {
-        Ok(response) => {
-            let latency = start.elapsed().as_millis() as u64;
-            if response.status().is_success() || response.status().is_redirection() {
-                HealthResult::online(connection_id, latency)
-            } else {
-                HealthResult::offline(
-                    connection_id,
-                    Some(format!("HTTP {}", response.status().as_u16())),
-                )
+    let follow_redirects = thresholds.redirect_status == StatusPolicy::Online;
+    let mut timing = HttpTiming::default();
+    let mut tls = None;
+
+    let exchange = async {
+        loop {
+            let mut response = send_probe(&url, &probe).await?;
+            timing.add_hop(&response.timing);
+            // The certificate that matters is the one served with the final
+            // response, not one from a redirect on the way there.
+            tls = response.tls.take();
+
+            match &response.location {
+                Some(location) if follow_redirects && response.status.is_redirection() => {
+                    if timing.redirects >= MAX_REDIRECTS {
+                        return Err(ProbeError::TooManyRedirects);
+                    }
+                    url = url
+                        .join(location)
+                        .map_err(|e| ProbeError::InvalidUrl(e.to_string()))?;
+                    if response.status == StatusCode::SEE_OTHER || probe.method == Method::POST {
+                        probe.method = Method::GET;
+                        probe.body = None;
+                    }
+                    // Never hand credentials to a different origin.
+                    if url.origin() != origin {
+                        if let Some((name, _)) = &auth.header {
+                            probe.headers.remove(name);
+                        }
+                        probe.identity = None;
+                    }
+                    timing.redirects += 1;
+                }
+                _ => return Ok::<_, ProbeError>(response),
             }
         }
-        Err(e) => HealthResult::offline(connection_id, Some(e.to_string())),
+    };
+
+    let response = match timeout(Duration::from_secs(TIMEOUT_SECS), exchange).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return HealthResult::offline(connection_id, Some(e.to_string())),
+        Err(_) => return HealthResult::offline(connection_id, Some("Request timed out".into())),
+    };
+
+    let latency = (timing.total_ms - timing.download_ms).round() as u64;
+    let result = evaluate_http_response(connection_id, &response, latency, check, thresholds)
+        .with_timing(timing);
+
+    match tls {
+        Some(info) => apply_tls_policy(result, info, thresholds),
+        None => result,
     }
 }
 
-async fn check_tcp_health(connection_id: uuid::Uuid, host: &str, port: u16) -> HealthResult {
-    let addr_str = format!("{}:{}", host, port);
+fn evaluate_http_response(
+    connection_id: uuid::Uuid,
+    response: &ProbeResponse,
+    latency: u64,
+    check: Option<&HttpCheckSpec>,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    let status = response.status;
+    let reason = format!("HTTP {}", status.as_u16());
+
+    if let Some(spec) = check {
+        let failures = evaluate_http_assertions(spec, status.as_u16(), &response.body);
+        if !failures.is_empty() {
+            let reason = failures.join("; ");
+            return match spec.on_failure {
+                StatusPolicy::Online => HealthResult::online(connection_id, latency),
+                StatusPolicy::Degraded => HealthResult::degraded(connection_id, latency, reason),
+                StatusPolicy::Offline => HealthResult::offline(connection_id, Some(reason)),
+            };
+        }
+
+        if !spec.expected_status.is_empty() {
+            return apply_latency_threshold(
+                HealthResult::online(connection_id, latency),
+                thresholds.http_latency_limit(),
+            );
+        }
+    }
+
+    match http_status_policy(status, thresholds) {
+        StatusPolicy::Online => apply_latency_threshold(
+            HealthResult::online(connection_id, latency),
+            thresholds.http_latency_limit(),
+        ),
+        StatusPolicy::Degraded => HealthResult::degraded(connection_id, latency, reason),
+        StatusPolicy::Offline => HealthResult::offline(connection_id, Some(reason)),
+    }
+}
 
-    let addr: SocketAddr = match addr_str.to_socket_addrs() {
+fn icmp_target(config: &ConnectionConfig) -> Option<String> {
+    match config {
+        ConnectionConfig::Website { url, .. } => reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string())),
+        ConnectionConfig::Ssh { host, .. }
+        | ConnectionConfig::Rdp { host, .. }
+        | ConnectionConfig::Vnc { host, .. }
+        | ConnectionConfig::Postgres { host, .. }
+        | ConnectionConfig::Mysql { host, .. }
+        | ConnectionConfig::Redis { host, .. }
+        | ConnectionConfig::Service { host, .. } => Some(host.clone()),
+        ConnectionConfig::Command { .. } | ConnectionConfig::Serial { .. } => None,
+    }
+}
+
+async fn check_icmp_health(
+    connection_id: uuid::Uuid,
+    host: &str,
+    count: u16,
+    thresholds: &HealthThresholds,
+) -> Option<HealthResult> {
+    let ip: IpAddr = match tokio::net::lookup_host((host, 0)).await {
         Ok(mut addrs) => match addrs.next() {
-            Some(addr) => addr,
+            Some(addr) => addr.ip(),
             None => {
-                return HealthResult::offline(connection_id, Some("Could not resolve host".into()))
+                return Some(HealthResult::offline(
+                    connection_id,
+                    Some("Could not resolve host".into()),
+                ))
             }
         },
-        Err(e) => return HealthResult::offline(connection_id, Some(e.to_string())),
+        Err(e) => return Some(HealthResult::offline(connection_id, Some(e.to_string()))),
+    };
+
+    let kind = if ip.is_ipv4() { ICMP::V4 } else { ICMP::V6 };
+    let client = Client::new(&Config::builder().kind(kind).build()).ok()?;
+
+    let ident = PingIdentifier(uuid::Uuid::new_v4().as_u128() as u16);
+    let mut pinger = client.pinger(ip, ident).await;
+    pinger.timeout(Duration::from_secs(PING_TIMEOUT_SECS));
+
+    let mut rtts = Vec::with_capacity(count as usize);
+    for seq in 0..count {
+        if seq > 0 {
+            sleep(Duration::from_millis(PING_INTERVAL_MS)).await;
+        }
+
+        match pinger.ping(PingSequence(seq), &PING_PAYLOAD).await {
+            Ok((_, rtt)) => rtts.push(rtt),
+            Err(SurgeError::IOError(e)) if e.kind() == ErrorKind::PermissionDenied => return None,
+            Err(_) => {}
+        }
+    }
+
+    let stats = summarize_pings(count, &rtts);
+
+    Some(match stats.avg_ms {
+        Some(avg) => {
+            let result = HealthResult::online(connection_id, avg.round() as u64);
+            let result = apply_packet_loss_threshold(result, &stats, thresholds.warn_packet_loss);
+            apply_latency_threshold(result, thresholds.warn_latency_ms).with_ping(stats)
+        }
+        None => HealthResult::offline(connection_id, Some("No ICMP echo replies".into()))
+            .with_ping(stats),
+    })
+}
+
+fn summarize_pings(sent: u16, rtts: &[Duration]) -> PingStats {
+    let received = rtts.len() as u16;
+    let packet_loss = if sent == 0 {
+        0.0
+    } else {
+        f64::from(sent - received) * 100.0 / f64::from(sent)
+    };
+
+    let millis: Vec<f64> = rtts.iter().map(|rtt| rtt.as_secs_f64() * 1000.0).collect();
+
+    if millis.is_empty() {
+        return PingStats {
+            sent,
+            received,
+            packet_loss,
+            min_ms: None,
+            avg_ms: None,
+            max_ms: None,
+            jitter_ms: None,
+        };
+    }
+
+    let min = millis.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = millis.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let avg = millis.iter().sum::<f64>() / millis.len() as f64;
+
+    // Mean absolute difference between consecutive round trips, as ping(8) and
+    // RFC 3550 do, rather than standard deviation.
+    let jitter = if millis.len() > 1 {
+        millis.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (millis.len() - 1) as f64
+    } else {
+        0.0
     };
 
+    PingStats {
+        sent,
+        received,
+        packet_loss,
+        min_ms: Some(min),
+        avg_ms: Some(avg),
+        max_ms: Some(max),
+        jitter_ms: Some(jitter),
+    }
+}
+
+fn http_status_policy(status: reqwest::StatusCode, thresholds: &HealthThresholds) -> StatusPolicy {
+    if status.is_success() {
+        StatusPolicy::Online
+    } else if status.is_redirection() {
+        thresholds.redirect_status
+    } else if status.is_client_error() {
+        thresholds.client_error_status
+    } else {
+        StatusPolicy::Offline
+    }
+}
+
+pub(crate) fn apply_latency_threshold(result: HealthResult, limit_ms: Option<u64>) -> HealthResult {
+    match (result.latency_ms, limit_ms) {
+        (Some(latency), Some(limit)) if latency > limit => result.degrade(format!(
+            "Slow response: {} ms (limit {} ms)",
+            latency, limit
+        )),
+        _ => result,
+    }
+}
+
+fn apply_packet_loss_threshold(
+    result: HealthResult,
+    stats: &PingStats,
+    limit: Option<f64>,
+) -> HealthResult {
+    match limit {
+        Some(limit) if stats.packet_loss > limit => {
+            result.degrade(format!("Packet loss {:.0}%", stats.packet_loss))
+        }
+        _ => result,
+    }
+}
+
+async fn check_tcp_health(
+    connection_id: uuid::Uuid,
+    host: &str,
+    port: u16,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    match connect_tcp(host, port).await {
+        Ok((_, latency)) => apply_latency_threshold(
+            HealthResult::online(connection_id, latency),
+            thresholds.warn_latency_ms,
+        ),
+        Err(e) => HealthResult::offline(connection_id, Some(e)),
+    }
+}
+
+pub(crate) fn resolve_addr(host: &str, port: u16) -> Result<SocketAddr, String> {
+    let addr_str = format!("{}:{}", host, port);
+
+    match addr_str.to_socket_addrs() {
+        Ok(mut addrs) => addrs
+            .next()
+            .ok_or_else(|| "Could not resolve host".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Connects within the check timeout and returns the stream together with the
+// connect latency in milliseconds, for checks that go on to talk to the
+// server.
+pub(crate) async fn connect_tcp(host: &str, port: u16) -> Result<(TcpStream, u64), String> {
+    let addr = resolve_addr(host, port)?;
     let start = Instant::now();
 
     match timeout(Duration::from_secs(TIMEOUT_SECS), TcpStream::connect(addr)).await {
-        Ok(Ok(_)) => {
-            let latency = start.elapsed().as_millis() as u64;
-            HealthResult::online(connection_id, latency)
-        }
-        Ok(Err(e)) => HealthResult::offline(connection_id, Some(e.to_string())),
-        Err(_) => HealthResult::offline(connection_id, Some("Connection timed out".into())),
+        Ok(Ok(stream)) => Ok((stream, start.elapsed().as_millis() as u64)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("Connection timed out".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HealthStatus;
+
+    #[test]
+    fn test_summarize_pings() {
+        // arrange
+        let rtts = [
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            Duration::from_millis(15),
+        ];
+
+        // act
+        let stats = summarize_pings(4, &rtts);
+
+        // assert
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.packet_loss, 25.0);
+        assert_eq!(stats.min_ms, Some(10.0));
+        assert_eq!(stats.max_ms, Some(20.0));
+        assert_eq!(stats.avg_ms, Some(15.0));
+        assert_eq!(stats.jitter_ms, Some(7.5));
+    }
+
+    #[test]
+    fn test_summarize_pings_all_lost() {
+        // arrange
+        let rtts: [Duration; 0] = [];
+
+        // act
+        let stats = summarize_pings(4, &rtts);
+
+        // assert
+        assert_eq!(stats.packet_loss, 100.0);
+        assert!(stats.avg_ms.is_none());
+        assert!(stats.jitter_ms.is_none());
+    }
+
+    #[test]
+    fn test_http_status_policy_defaults() {
+        // arrange
+        let thresholds = HealthThresholds::default();
+
+        // act
+        let redirect = http_status_policy(reqwest::StatusCode::FOUND, &thresholds);
+        let not_found = http_status_policy(reqwest::StatusCode::NOT_FOUND, &thresholds);
+
+        // assert
+        assert_eq!(redirect, StatusPolicy::Online);
+        assert_eq!(not_found, StatusPolicy::Offline);
+    }
+
+    #[test]
+    fn test_http_status_policy_degraded_client_error() {
+        // arrange
+        let thresholds = HealthThresholds {
+            client_error_status: StatusPolicy::Degraded,
+            ..Default::default()
+        };
+
+        // act
+        let policy = http_status_policy(reqwest::StatusCode::UNAUTHORIZED, &thresholds);
+
+        // assert
+        assert_eq!(policy, StatusPolicy::Degraded);
+    }
+
+    #[test]
+    fn test_apply_latency_threshold() {
+        // arrange
+        let result = HealthResult::online(uuid::Uuid::new_v4(), 450);
+
+        // act
+        let result = apply_latency_threshold(result, Some(200));
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Degraded);
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn test_http_latency_limit_falls_back_to_warn_latency() {
+        // arrange
+        let general = HealthThresholds {
+            warn_latency_ms: Some(300),
+            ..Default::default()
+        };
+        let both = HealthThresholds {
+            slow_response_ms: Some(1500),
+            ..general.clone()
+        };
+
+        // act
+        let limits = [general.http_latency_limit(), both.http_latency_limit()];
+
+        // assert
+        assert_eq!(limits, [Some(300), Some(1500)]);
+    }
+
+    #[test]
+    fn test_apply_packet_loss_threshold_ignores_offline() {
+        // arrange
+        let stats = summarize_pings(4, &[]);
+        let result = HealthResult::offline(uuid::Uuid::new_v4(), None);
+
+        // act
+        let result = apply_packet_loss_threshold(result, &stats, Some(10.0));
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Offline);
+    }
+
+    async fn serve_once(response: &'static str) -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_http_check_applies_json_assertion() {
+        // arrange
+        let port = serve_once(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 21\r\nconnection: close\r\n\r\n{\"status\":\"degraded\"}",
+        )
+        .await;
+        let spec = HttpCheckSpec {
+            method: None,
+            headers: Default::default(),
+            body: None,
+            expected_status: Vec::new(),
+            body_contains: None,
+            body_excludes: None,
+            json: Some(crate::models::JsonAssertion {
+                path: "status".into(),
+                equals: serde_json::json!("ok"),
+            }),
+            on_failure: StatusPolicy::Degraded,
+        };
+        let url = format!("http://127.0.0.1:{}", port);
+
+        // act
+        let result = check_http_health(
+            uuid::Uuid::new_v4(),
+            &url,
+            None,
+            Some(&spec),
+            &ResolvedAuth::default(),
+            false,
+            &HealthThresholds::default(),
+        )
+        .await;
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Degraded);
+        assert_eq!(
+            result.error.as_deref(),
+            Some(r#"status is "degraded", expected "ok""#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_check_reports_timing() {
+        // arrange
+        let port =
+            serve_once("HTTP/1.1 204 No Content\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await;
+        let url = format!("http://127.0.0.1:{}", port);
+
+        // act
+        let result = check_http_health(
+            uuid::Uuid::new_v4(),
+            &url,
+            None,
+            None,
+            &ResolvedAuth::default(),
+            false,
+            &HealthThresholds::default(),
+        )
+        .await;
+
+        // assert
+        let timing = result.timing.expect("timing recorded");
+        assert_eq!(result.status, HealthStatus::Online);
+        assert_eq!(timing.tls_ms, None);
+        assert_eq!(timing.redirects, 0);
+        assert!(timing.total_ms >= timing.ttfb_ms);
+    }
+
+    #[tokio::test]
+    async fn test_http_check_stops_at_redirect_limit() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // arrange
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 302 Found\r\nlocation: /again\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .await;
+            }
+        });
+        let url = format!("http://127.0.0.1:{}", port);
+
+        // act
+        let result = check_http_health(
+            uuid::Uuid::new_v4(),
+            &url,
+            None,
+            None,
+            &ResolvedAuth::default(),
+            false,
+            &HealthThresholds::default(),
+        )
+        .await;
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Offline);
+        assert_eq!(result.error.as_deref(), Some("Too many redirects"));
+    }
+
+    #[test]
+    fn test_icmp_target_from_url() {
+        // arrange
+        let config = ConnectionConfig::Website {
+            url: "https://nas.local:5001/dashboard".into(),
+            check_path: None,
+            strict_tls: false,
+            check: None,
+            auth: None,
+            auth_secret: None,
+        };
+
+        // act
+        let host = icmp_target(&config);
+
+        // assert
+        assert_eq!(host.as_deref(), Some("nas.local"));
     }
 }
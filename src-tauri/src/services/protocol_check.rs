@@ -0,0 +1,489 @@
+use crate::models::{HealthResult, HealthThresholds};
+use crate::services::ping::{apply_latency_threshold, connect_tcp, TIMEOUT_SECS};
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+// Larger than any greeting or status reply we expect; anything bigger means
+// we are not talking to the server we think we are.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+const PG_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+const PG_PROTOCOL_3_0: u32 = 196608;
+// SQLSTATEs for "starting up / shutting down" and "too many connections".
+const PG_BUSY_STATES: &[&str] = &["57P03", "53300"];
+const MYSQL_TOO_MANY_CONNECTIONS: u16 = 1040;
+
+// What a protocol exchange found out about the server. Errors mean it is not
+// (or not yet) speaking the expected protocol.
+enum Handshake {
+    // Answering normally; the message names the server and its version.
+    Ready(String),
+    // Speaking the protocol but turning clients away for now.
+    Busy(String),
+}
+
+async fn check_handshake<F, Fut>(
+    connection_id: Uuid,
+    host: &str,
+    port: u16,
+    thresholds: &HealthThresholds,
+    handshake: F,
+) -> HealthResult
+where
+    F: FnOnce(TcpStream) -> Fut,
+    Fut: Future<Output = Result<Handshake, String>>,
+{
+    let (stream, latency) = match connect_tcp(host, port).await {
+        Ok(connected) => connected,
+        Err(e) => return HealthResult::offline(connection_id, Some(e)),
+    };
+
+    match timeout(Duration::from_secs(TIMEOUT_SECS), handshake(stream)).await {
+        Ok(Ok(Handshake::Ready(message))) => apply_latency_threshold(
+            HealthResult::online(connection_id, latency),
+            thresholds.warn_latency_ms,
+        )
+        .with_message(message),
+        Ok(Ok(Handshake::Busy(reason))) => HealthResult::degraded(connection_id, latency, reason),
+        Ok(Err(e)) => HealthResult::offline(connection_id, Some(e)),
+        Err(_) => HealthResult::offline(
+            connection_id,
+            Some("Timed out waiting for the server".into()),
+        ),
+    }
+}
+
+// Connects and waits for the server's "RFB xxx.yyy\n" greeting, so an open
+// port held by something other than a VNC server does not count as online.
+pub async fn check_vnc_health(
+    connection_id: Uuid,
+    host: &str,
+    port: u16,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    check_handshake(
+        connection_id,
+        host,
+        port,
+        thresholds,
+        |mut stream| async move {
+            let mut banner = [0u8; 12];
+            stream
+                .read_exact(&mut banner)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            match parse_rfb_version(&banner) {
+                Some((major, minor)) => Ok(Handshake::Ready(format!("RFB {}.{}", major, minor))),
+                None => Err(format!(
+                    "Not a VNC server (got \"{}\")",
+                    String::from_utf8_lossy(&banner).trim_end()
+                )),
+            }
+        },
+    )
+    .await
+}
+
+fn parse_rfb_version(banner: &[u8; 12]) -> Option<(u16, u16)> {
+    let banner = std::str::from_utf8(banner).ok()?;
+    let version = banner.strip_prefix("RFB ")?.strip_suffix('\n')?;
+    let (major, minor) = version.split_once('.')?;
+    if major.len() != 3 || minor.len() != 3 {
+        return None;
+    }
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+// Sends an SSLRequest, which any PostgreSQL server answers with a single 'S'
+// or 'N', then a startup message. The server only reports its version once
+// the login succeeds, so with password authentication the check stops at the
+// authentication request and the version stays unknown.
+pub async fn check_postgres_health(
+    connection_id: Uuid,
+    host: &str,
+    port: u16,
+    username: Option<&str>,
+    database: Option<&str>,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    let username = username.unwrap_or("postgres").to_string();
+    let database = database.map(str::to_string);
+    let reconnect_host = host.to_string();
+
+    check_handshake(
+        connection_id,
+        host,
+        port,
+        thresholds,
+        |mut stream| async move {
+            stream
+                .write_all(&PG_SSL_REQUEST)
+                .await
+                .map_err(|e| e.to_string())?;
+            let stream = match stream.read_u8().await.map_err(|e| e.to_string())? {
+                b'N' => stream,
+                // We would have to start TLS to go on, so ask again in the clear.
+                b'S' => connect_tcp(&reconnect_host, port).await?.0,
+                _ => return Err("Not a PostgreSQL server".into()),
+            };
+            postgres_startup(stream, &username, database.as_deref()).await
+        },
+    )
+    .await
+}
+
+async fn postgres_startup(
+    mut stream: TcpStream,
+    username: &str,
+    database: Option<&str>,
+) -> Result<Handshake, String> {
+    let mut body = PG_PROTOCOL_3_0.to_be_bytes().to_vec();
+    for (key, value) in [
+        ("user", Some(username)),
+        ("database", database),
+        ("application_name", Some("turbo-waffle")),
+    ] {
+        if let Some(value) = value {
+            body.extend_from_slice(key.as_bytes());
+            body.push(0);
+            body.extend_from_slice(value.as_bytes());
+            body.push(0);
+        }
+    }
+    body.push(0);
+    let mut startup = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+    startup.extend(body);
+    stream
+        .write_all(&startup)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    loop {
+        let (tag, body) = read_postgres_message(&mut stream).await?;
+        match tag {
+            b'R' if body.get(..4) == Some(&[0, 0, 0, 0]) => continue,
+            b'R' => {
+                return Ok(Handshake::Ready(
+                    "PostgreSQL (version hidden until login)".into(),
+                ))
+            }
+            b'S' => {
+                let mut fields = body.split(|b| *b == 0);
+                if fields.next() == Some(b"server_version") {
+                    let version = String::from_utf8_lossy(fields.next().unwrap_or_default());
+                    // Terminate, so the server does not log an unexpected EOF.
+                    let _ = stream.write_all(&[b'X', 0, 0, 0, 4]).await;
+                    return Ok(Handshake::Ready(format!("PostgreSQL {}", version)));
+                }
+            }
+            b'E' => {
+                let (code, message) = parse_postgres_error(&body);
+                return if PG_BUSY_STATES.contains(&code.as_str()) {
+                    Ok(Handshake::Busy(message))
+                } else {
+                    Ok(Handshake::Ready(format!("PostgreSQL ({})", message)))
+                };
+            }
+            b'Z' => return Ok(Handshake::Ready("PostgreSQL".into())),
+            _ => continue,
+        }
+    }
+}
+
+async fn read_postgres_message(stream: &mut TcpStream) -> Result<(u8, Vec<u8>), String> {
+    let tag = stream.read_u8().await.map_err(|e| e.to_string())?;
+    let len = stream.read_u32().await.map_err(|e| e.to_string())? as usize;
+    if !(4..=MAX_MESSAGE_LEN).contains(&len) {
+        return Err("Not a PostgreSQL server".into());
+    }
+    let mut body = vec![0u8; len - 4];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((tag, body))
+}
+
+// Returns the SQLSTATE and message fields of an ErrorResponse.
+fn parse_postgres_error(body: &[u8]) -> (String, String) {
+    let mut code = String::new();
+    let mut message = String::new();
+    for field in body.split(|b| *b == 0).filter(|f| !f.is_empty()) {
+        let value = String::from_utf8_lossy(&field[1..]).into_owned();
+        match field[0] {
+            b'C' => code = value,
+            b'M' => message = value,
+            _ => {}
+        }
+    }
+    (code, message)
+}
+
+// MySQL and MariaDB greet every client with a handshake packet that starts
+// with the protocol version (10) and the server version string, or with an
+// error packet when they refuse the connection outright.
+pub async fn check_mysql_health(
+    connection_id: Uuid,
+    host: &str,
+    port: u16,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    check_handshake(
+        connection_id,
+        host,
+        port,
+        thresholds,
+        |mut stream| async move {
+            let mut header = [0u8; 4];
+            stream
+                .read_exact(&mut header)
+                .await
+                .map_err(|e| e.to_string())?;
+            let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            if !(1..=MAX_MESSAGE_LEN).contains(&len) {
+                return Err("Not a MySQL server".into());
+            }
+            let mut payload = vec![0u8; len];
+            stream
+                .read_exact(&mut payload)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            match payload[0] {
+                10 => {
+                    let version = payload[1..].split(|b| *b == 0).next().unwrap_or_default();
+                    Ok(Handshake::Ready(mysql_server_name(
+                        &String::from_utf8_lossy(version),
+                    )))
+                }
+                0xff if len >= 3 => {
+                    let code = u16::from_le_bytes([payload[1], payload[2]]);
+                    let message = String::from_utf8_lossy(&payload[3..]).into_owned();
+                    if code == MYSQL_TOO_MANY_CONNECTIONS {
+                        Ok(Handshake::Busy(message))
+                    } else {
+                        Ok(Handshake::Ready(format!("MySQL ({})", message)))
+                    }
+                }
+                _ => Err("Not a MySQL server".into()),
+            }
+        },
+    )
+    .await
+}
+
+// MariaDB prefixes its version with "5.5.5-" for the sake of old clients.
+fn mysql_server_name(version: &str) -> String {
+    if version.contains("MariaDB") {
+        let version = version.trim_start_matches("5.5.5-");
+        format!("MariaDB {}", version.split('-').next().unwrap_or(version))
+    } else {
+        format!("MySQL {}", version)
+    }
+}
+
+// Pipelines PING and INFO server. A server with a password answers both with
+// NOAUTH, which still shows it is up.
+pub async fn check_redis_health(
+    connection_id: Uuid,
+    host: &str,
+    port: u16,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    check_handshake(
+        connection_id,
+        host,
+        port,
+        thresholds,
+        |mut stream| async move {
+            stream
+                .write_all(b"*1\r\n$4\r\nPING\r\n*2\r\n$4\r\nINFO\r\n$6\r\nserver\r\n")
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut reader = BufReader::new(stream);
+
+            let pong = read_redis_line(&mut reader).await?;
+            if let Some(error) = pong.strip_prefix('-') {
+                return if error.starts_with("NOAUTH") || error.starts_with("WRONGPASS") {
+                    Ok(Handshake::Ready("Redis (authentication required)".into()))
+                } else {
+                    Ok(Handshake::Busy(error.to_string()))
+                };
+            }
+            if pong != "+PONG" {
+                return Err("Not a Redis server".into());
+            }
+
+            // The PONG settles it; INFO is only for the version, and ACLs
+            // (-NOPERM) or a renamed command (-ERR) can refuse it.
+            let version = read_redis_version(&mut reader).await.ok().flatten();
+            Ok(Handshake::Ready(match version {
+                Some(version) => format!("Redis {}", version),
+                None => "Redis".into(),
+            }))
+        },
+    )
+    .await
+}
+
+async fn read_redis_version(reader: &mut BufReader<TcpStream>) -> Result<Option<String>, String> {
+    let info_len = read_redis_line(reader)
+        .await?
+        .strip_prefix('$')
+        .and_then(|len| len.parse::<usize>().ok())
+        .filter(|len| *len <= MAX_MESSAGE_LEN)
+        .ok_or("Unexpected reply to INFO")?;
+    let mut info = vec![0u8; info_len];
+    reader
+        .read_exact(&mut info)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(String::from_utf8_lossy(&info)
+        .lines()
+        .find_map(|line| line.strip_prefix("redis_version:").map(str::to_string)))
+}
+
+async fn read_redis_line(reader: &mut BufReader<TcpStream>) -> Result<String, String> {
+    let mut line = String::new();
+    let read = reader
+        .read_line(&mut line)
+        .await
+        .map_err(|e| e.to_string())?;
+    if read == 0 {
+        return Err("Connection closed by server".into());
+    }
+    Ok(line.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HealthStatus;
+    use tokio::net::TcpListener;
+
+    // Sends `greeting` straight away and then reads until the client hangs up,
+    // which is enough for servers whose replies do not depend on the request.
+    async fn greet_once(greeting: Vec<u8>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.write_all(&greeting).await;
+            let _ = tokio::io::copy(&mut stream, &mut tokio::io::sink()).await;
+        });
+        port
+    }
+
+    fn pg_message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend(((body.len() + 4) as u32).to_be_bytes());
+        message.extend(body);
+        message
+    }
+
+    #[tokio::test]
+    async fn test_vnc_check_reads_rfb_banner() {
+        // arrange
+        let vnc = greet_once(b"RFB 003.008\n".to_vec()).await;
+        let ssh = greet_once(b"SSH-2.0-OpenSSH_9.6\r\n".to_vec()).await;
+        let id = Uuid::new_v4();
+
+        // act
+        let vnc_result = check_vnc_health(id, "127.0.0.1", vnc, &HealthThresholds::default()).await;
+        let ssh_result = check_vnc_health(id, "127.0.0.1", ssh, &HealthThresholds::default()).await;
+
+        // assert
+        assert_eq!(vnc_result.status, HealthStatus::Online);
+        assert_eq!(vnc_result.message.as_deref(), Some("RFB 3.8"));
+        assert_eq!(ssh_result.status, HealthStatus::Offline);
+        assert_eq!(
+            ssh_result.error.as_deref(),
+            Some("Not a VNC server (got \"SSH-2.0-Open\")")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_postgres_check_reports_server_version() {
+        // arrange
+        let mut greeting = b"N".to_vec();
+        greeting.extend(pg_message(b'R', &[0, 0, 0, 0]));
+        greeting.extend(pg_message(b'S', b"server_version\x0016.2\x00"));
+        greeting.extend(pg_message(b'Z', b"I"));
+        let trusted = greet_once(greeting).await;
+        let mut greeting = b"N".to_vec();
+        greeting.extend(pg_message(
+            b'E',
+            b"SFATAL\x00C57P03\x00Mthe database system is starting up\x00\x00",
+        ));
+        let starting = greet_once(greeting).await;
+        let id = Uuid::new_v4();
+        let thresholds = HealthThresholds::default();
+
+        // act
+        let trusted =
+            check_postgres_health(id, "127.0.0.1", trusted, None, None, &thresholds).await;
+        let starting =
+            check_postgres_health(id, "127.0.0.1", starting, None, None, &thresholds).await;
+
+        // assert
+        assert_eq!(trusted.status, HealthStatus::Online);
+        assert_eq!(trusted.message.as_deref(), Some("PostgreSQL 16.2"));
+        assert_eq!(starting.status, HealthStatus::Degraded);
+        assert_eq!(
+            starting.error.as_deref(),
+            Some("the database system is starting up")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mysql_and_redis_checks_report_server_version() {
+        // arrange
+        let payload = b"\x0a5.5.5-10.11.6-MariaDB-0+deb12u1\x00\x08\x00\x00\x00";
+        let mut greeting = vec![payload.len() as u8, 0, 0, 0];
+        greeting.extend(payload);
+        let mysql = greet_once(greeting).await;
+        let info = "# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\n";
+        let redis =
+            greet_once(format!("+PONG\r\n${}\r\n{}\r\n", info.len(), info).into_bytes()).await;
+        let id = Uuid::new_v4();
+        let thresholds = HealthThresholds::default();
+
+        // act
+        let mysql = check_mysql_health(id, "127.0.0.1", mysql, &thresholds).await;
+        let redis = check_redis_health(id, "127.0.0.1", redis, &thresholds).await;
+
+        // assert
+        assert_eq!(mysql.message.as_deref(), Some("MariaDB 10.11.6"));
+        assert_eq!(redis.status, HealthStatus::Online);
+        assert_eq!(redis.message.as_deref(), Some("Redis 7.2.4"));
+        assert_eq!(mysql_server_name("8.0.36"), "MySQL 8.0.36");
+    }
+
+    #[tokio::test]
+    async fn test_redis_check_without_info_permission() {
+        // arrange
+        let redis = greet_once(
+            b"+PONG\r\n-NOPERM this user has no permissions to run the 'info' command\r\n".to_vec(),
+        )
+        .await;
+
+        // act
+        let result = check_redis_health(
+            Uuid::new_v4(),
+            "127.0.0.1",
+            redis,
+            &HealthThresholds::default(),
+        )
+        .await;
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Online);
+        assert_eq!(result.message.as_deref(), Some("Redis"));
+    }
+}
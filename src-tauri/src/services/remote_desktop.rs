@@ -0,0 +1,352 @@
+use crate::services::{open_in_terminal, TerminalError};
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+use thiserror::Error;
+#[cfg(target_os = "linux")]
+use tokio::time::timeout;
+
+#[derive(Error, Debug)]
+pub enum RemoteDesktopError {
+    #[error("No {0} client found")]
+    NoClientFound(&'static str),
+    #[error("Failed to launch {client}: {reason}")]
+    LaunchFailed { client: String, reason: String },
+}
+
+pub struct RdpSession<'a> {
+    pub host: &'a str,
+    pub port: u16,
+    pub username: Option<&'a str>,
+    pub domain: Option<&'a str>,
+    pub resolution: Option<&'a str>,
+    pub fullscreen: bool,
+    pub gateway: Option<&'a str>,
+}
+
+#[cfg(target_os = "linux")]
+const LINUX_FREERDP_CLIENTS: &[&str] = &["xfreerdp3", "xfreerdp", "wlfreerdp3", "wlfreerdp"];
+#[cfg(target_os = "linux")]
+const VERSION_TIMEOUT_SECS: u64 = 5;
+
+pub async fn open_rdp(session: &RdpSession<'_>) -> Result<(), RemoteDesktopError> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(client) = LINUX_FREERDP_CLIENTS
+            .iter()
+            .find(|client| which::which(client).is_ok())
+        {
+            let args = freerdp_args(session, freerdp_version(client).await);
+            // Run in a terminal so FreeRDP can ask for the password (and
+            // about a changed certificate); without one it still connects
+            // when no prompt is needed.
+            return match open_in_terminal(client, &args) {
+                Ok(()) => Ok(()),
+                Err(TerminalError::NoTerminalFound) => spawn(client, &args),
+                Err(TerminalError::LaunchFailed(reason)) => Err(RemoteDesktopError::LaunchFailed {
+                    client: client.to_string(),
+                    reason,
+                }),
+            };
+        }
+        if which::which("remmina").is_ok() {
+            return spawn("remmina", &["-c".to_string(), remmina_rdp_uri(session)]);
+        }
+        Err(RemoteDesktopError::NoClientFound("RDP"))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        spawn("open", &[macos_rdp_uri(session)])
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = session;
+        Err(RemoteDesktopError::NoClientFound("RDP"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+const LINUX_VNC_VIEWERS: &[&str] = &["vncviewer", "xtigervncviewer"];
+
+pub fn open_vnc(host: &str, port: u16, view_only: bool) -> Result<(), RemoteDesktopError> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(viewer) = LINUX_VNC_VIEWERS
+            .iter()
+            .find(|viewer| which::which(viewer).is_ok())
+        {
+            return spawn(viewer, &vncviewer_args(host, port, view_only));
+        }
+        if which::which("remmina").is_ok() {
+            return spawn("remmina", &["-c".to_string(), vnc_uri(host, port)]);
+        }
+        Err(RemoteDesktopError::NoClientFound("VNC"))
+    }
+
+    // Screen Sharing has no view-only switch on the command line.
+    #[cfg(target_os = "macos")]
+    {
+        let _ = view_only;
+        spawn("open", &[vnc_uri(host, port)])
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = (host, port, view_only);
+        Err(RemoteDesktopError::NoClientFound("VNC"))
+    }
+}
+
+fn spawn(client: &str, args: &[String]) -> Result<(), RemoteDesktopError> {
+    Command::new(client)
+        .args(args)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| RemoteDesktopError::LaunchFailed {
+            client: client.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+// Accepts "1920x1080" (or with a capital X / spaces around it).
+pub fn parse_resolution(resolution: &str) -> Option<(u32, u32)> {
+    let (width, height) = resolution
+        .to_lowercase()
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))?;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+// Distributions that ship both majors suffix the 3.x binaries; otherwise ask
+// the client, since `xfreerdp` can be either. The answer is kept for the rest
+// of the run rather than starting the client again on every connect.
+#[cfg(target_os = "linux")]
+async fn freerdp_version(client: &'static str) -> u32 {
+    static VERSIONS: Mutex<BTreeMap<&str, u32>> = Mutex::new(BTreeMap::new());
+
+    if client.ends_with('3') {
+        return 3;
+    }
+    if let Some(version) = VERSIONS.lock().unwrap().get(client) {
+        return *version;
+    }
+
+    let output = tokio::process::Command::new(client)
+        .arg("--version")
+        .kill_on_drop(true)
+        .output();
+    let version = timeout(Duration::from_secs(VERSION_TIMEOUT_SECS), output)
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .and_then(|output| parse_freerdp_version(&String::from_utf8_lossy(&output.stdout)));
+    // A client that didn't answer in time is asked again next time.
+    match version {
+        Some(version) => *VERSIONS.lock().unwrap().entry(client).or_insert(version),
+        None => 2,
+    }
+}
+
+// "This is FreeRDP version 2.11.5 (2.11.5)"
+pub fn parse_freerdp_version(output: &str) -> Option<u32> {
+    let version = output.split("version").nth(1)?.trim_start();
+    version.split('.').next()?.trim().parse().ok()
+}
+
+pub fn freerdp_args(session: &RdpSession, major_version: u32) -> Vec<String> {
+    // Trust the certificate on first use instead of prompting on every
+    // connection; 2.x spells the option differently.
+    let cert = if major_version >= 3 {
+        "/cert:tofu"
+    } else {
+        "/cert-tofu"
+    };
+    let mut args = vec![
+        format!("/v:{}:{}", session.host, session.port),
+        cert.to_string(),
+    ];
+    if let Some(username) = non_empty(session.username) {
+        args.push(format!("/u:{}", username));
+    }
+    if let Some(domain) = non_empty(session.domain) {
+        args.push(format!("/d:{}", domain));
+    }
+    if session.fullscreen {
+        args.push("/f".to_string());
+    } else if let Some((width, height)) = session.resolution.and_then(parse_resolution) {
+        args.push(format!("/size:{}x{}", width, height));
+    }
+    if let Some(gateway) = non_empty(session.gateway) {
+        args.push(format!("/g:{}", gateway));
+    }
+    args
+}
+
+// Remmina only takes the address and user from the command line; the other
+// options are left to its defaults.
+pub fn remmina_rdp_uri(session: &RdpSession) -> String {
+    let user = match (non_empty(session.domain), non_empty(session.username)) {
+        (Some(domain), Some(username)) => format!("{}\\{}@", domain, username),
+        (None, Some(username)) => format!("{}@", username),
+        _ => String::new(),
+    };
+    format!("rdp://{}{}:{}", user, session.host, session.port)
+}
+
+// The URI scheme understood by Microsoft Remote Desktop / Windows App, which
+// takes .rdp file settings as `name=type:value` pairs.
+pub fn macos_rdp_uri(session: &RdpSession) -> String {
+    let mut settings = vec![format!("full address=s:{}:{}", session.host, session.port)];
+    if let Some(username) = non_empty(session.username) {
+        settings.push(format!("username=s:{}", username));
+    }
+    if let Some(domain) = non_empty(session.domain) {
+        settings.push(format!("domain=s:{}", domain));
+    }
+    if session.fullscreen {
+        settings.push("screen mode id=i:2".to_string());
+    } else if let Some((width, height)) = session.resolution.and_then(parse_resolution) {
+        settings.push("screen mode id=i:1".to_string());
+        settings.push(format!("desktopwidth=i:{}", width));
+        settings.push(format!("desktopheight=i:{}", height));
+    }
+    if let Some(gateway) = non_empty(session.gateway) {
+        settings.push(format!("gatewayhostname=s:{}", gateway));
+        settings.push("gatewayusagemethod=i:1".to_string());
+    }
+
+    let query: Vec<String> = settings.iter().map(|s| percent_encode(s)).collect();
+    format!("rdp://{}", query.join("&"))
+}
+
+// TigerVNC, TightVNC and RealVNC all take `host::port` (two colons for a port
+// rather than a display number) and the case-insensitive `-ViewOnly` flag.
+pub fn vncviewer_args(host: &str, port: u16, view_only: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    if view_only {
+        args.push("-ViewOnly".to_string());
+    }
+    args.push(format!("{}::{}", host, port));
+    args
+}
+
+pub fn vnc_uri(host: &str, port: u16) -> String {
+    format!("vnc://{}:{}", host, port)
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+// Leaves the `=` and `:` separators of each setting readable.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'=' | b':' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> RdpSession<'static> {
+        RdpSession {
+            host: "win11.lab",
+            port: 3390,
+            username: Some("alice"),
+            domain: Some("CORP"),
+            resolution: Some("1920x1080"),
+            fullscreen: false,
+            gateway: Some("gw.corp.example"),
+        }
+    }
+
+    #[test]
+    fn test_freerdp_args() {
+        // arrange
+        let windowed = session();
+        let fullscreen = RdpSession {
+            fullscreen: true,
+            domain: Some(" "),
+            gateway: None,
+            ..session()
+        };
+
+        // act
+        let windowed_args = freerdp_args(&windowed, 3);
+        let fullscreen_args = freerdp_args(&fullscreen, 2);
+
+        // assert
+        assert_eq!(
+            windowed_args,
+            vec![
+                "/v:win11.lab:3390",
+                "/cert:tofu",
+                "/u:alice",
+                "/d:CORP",
+                "/size:1920x1080",
+                "/g:gw.corp.example",
+            ]
+        );
+        assert_eq!(
+            fullscreen_args,
+            vec!["/v:win11.lab:3390", "/cert-tofu", "/u:alice", "/f"]
+        );
+    }
+
+    #[test]
+    fn test_parse_freerdp_version() {
+        // arrange
+        let v2 = "This is FreeRDP version 2.11.5 (2.11.5)\n";
+        let v3 = "This is FreeRDP version 3.5.1 (3.5.1)\nBuild configuration: ...";
+
+        // act
+        let versions = [v2, v3, "garbage"].map(parse_freerdp_version);
+
+        // assert
+        assert_eq!(versions, [Some(2), Some(3), None]);
+    }
+
+    #[test]
+    fn test_rdp_uris() {
+        // arrange
+        let session = session();
+
+        // act
+        let remmina = remmina_rdp_uri(&session);
+        let macos = macos_rdp_uri(&session);
+
+        // assert
+        assert_eq!(remmina, r"rdp://CORP\alice@win11.lab:3390");
+        assert_eq!(
+            macos,
+            "rdp://full%20address=s:win11.lab:3390&username=s:alice&domain=s:CORP\
+             &screen%20mode%20id=i:1&desktopwidth=i:1920&desktopheight=i:1080\
+             &gatewayhostname=s:gw.corp.example&gatewayusagemethod=i:1"
+        );
+        assert_eq!(parse_resolution("1280 X 720"), Some((1280, 720)));
+        assert_eq!(parse_resolution("0x720"), None);
+    }
+
+    #[test]
+    fn test_vnc_launch_args() {
+        // arrange
+        let host = "pi.lab";
+
+        // act
+        let viewer = vncviewer_args(host, 5901, true);
+        let uri = vnc_uri(host, 5901);
+
+        // assert
+        assert_eq!(viewer, vec!["-ViewOnly", "pi.lab::5901"]);
+        assert_eq!(uri, "vnc://pi.lab:5901");
+    }
+}
@@ -0,0 +1,359 @@
+use crate::models::{AlertEvent, Connection, HealthResult, HealthStatus};
+use crate::services::{
+    check_connection_health, in_quiet_hours, notify_transition, AlertDispatcher, TransitionTracker,
+};
+use crate::storage::{ConnectionRepository, HistoryStorage, SecretStorage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::sync::{watch, Notify};
+use tokio::time::{sleep_until, Instant};
+use uuid::Uuid;
+
+pub const HEALTH_CHANGED_EVENT: &str = "health-changed";
+
+// Who asked for a check. Only scheduled checks count toward the failures
+// needed before a connection is reported down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckOrigin {
+    Scheduled,
+    Manual,
+}
+
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const MIN_INTERVAL_SECS: u64 = 10;
+const IDLE_WAKE_SECS: u64 = 60;
+const HISTORY_COMPACT_SECS: u64 = 6 * 60 * 60;
+
+#[derive(Clone, Default)]
+pub struct HealthScheduler {
+    latest: Arc<Mutex<HashMap<Uuid, HealthResult>>>,
+    next_due: Arc<Mutex<HashMap<Uuid, Instant>>>,
+    // Bumped whenever a connection's pending check is superseded, so results
+    // from checks started before then can be told apart and dropped.
+    generations: Arc<Mutex<HashMap<Uuid, u64>>>,
+    wake: Arc<Notify>,
+    history: Option<Arc<HistoryStorage>>,
+    transitions: Arc<Mutex<TransitionTracker>>,
+    results: watch::Sender<()>,
+}
+
+impl HealthScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_history(mut self, history: HistoryStorage) -> Self {
+        self.history = Some(Arc::new(history));
+        self
+    }
+
+    pub fn start<R: Runtime>(&self, app: AppHandle<R>) {
+        let scheduler = self.clone();
+        tauri::async_runtime::spawn(async move {
+            scheduler.run(app).await;
+        });
+
+        if let Some(history) = self.history.clone() {
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(HISTORY_COMPACT_SECS));
+                loop {
+                    interval.tick().await;
+                    let history = history.clone();
+                    let _ = tauri::async_runtime::spawn_blocking(move || {
+                        history.compact(chrono::Utc::now())
+                    })
+                    .await;
+                }
+            });
+        }
+    }
+
+    pub fn latest(&self) -> Vec<HealthResult> {
+        self.latest.lock().unwrap().values().cloned().collect()
+    }
+
+    // Fires after every recorded result, not only on status changes, so
+    // latency shown elsewhere stays current.
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.results.subscribe()
+    }
+
+    pub fn check_soon(&self, id: Uuid) {
+        self.supersede(id);
+        self.next_due.lock().unwrap().remove(&id);
+        self.wake.notify_one();
+    }
+
+    pub fn forget(&self, id: Uuid) {
+        self.supersede(id);
+        self.latest.lock().unwrap().remove(&id);
+        self.transitions.lock().unwrap().forget(id);
+        self.next_due.lock().unwrap().remove(&id);
+        self.wake.notify_one();
+
+        if let Some(history) = self.history.clone() {
+            tauri::async_runtime::spawn_blocking(move || {
+                let _ = history.remove(id);
+            });
+        }
+    }
+
+    pub fn report<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        result: HealthResult,
+        origin: CheckOrigin,
+    ) {
+        let generation = self.generation(result.connection_id);
+        self.report_checked(app, result, origin, generation);
+    }
+
+    // `generation` is the connection's generation when its check started. A
+    // result from an earlier one checked a config that has since been saved
+    // over (or a connection since forgotten), so it is dropped rather than
+    // overwriting the result of the check that replaced it.
+    fn report_checked<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        result: HealthResult,
+        origin: CheckOrigin,
+        generation: u64,
+    ) {
+        // A check that finishes after its connection was deleted is dropped,
+        // so the connection doesn't reappear in the history or the latest
+        // results.
+        let known = app
+            .try_state::<ConnectionRepository>()
+            .is_some_and(|repository| repository.get(result.connection_id).is_some());
+        if !known {
+            return;
+        }
+        let Some(previous) = self.record(result.clone(), generation) else {
+            return;
+        };
+
+        // Appending is file IO, so it runs off the async workers.
+        if let Some(history) = self.history.clone() {
+            let entry = result.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let _ = history.append(&entry);
+            });
+        }
+
+        self.notify(app, &result, origin);
+
+        if previous.as_ref() != Some(&result.status) {
+            let _ = app.emit(HEALTH_CHANGED_EVENT, &result);
+            // The first result after startup is not a change worth alerting on.
+            if let Some(previous) = previous {
+                self.alert(app, previous, &result);
+            }
+        }
+    }
+
+    fn alert<R: Runtime>(&self, app: &AppHandle<R>, previous: HealthStatus, result: &HealthResult) {
+        let (Some(repository), Some(dispatcher)) = (
+            app.try_state::<ConnectionRepository>(),
+            app.try_state::<AlertDispatcher>(),
+        ) else {
+            return;
+        };
+        let mut channels = repository.settings().alert_channels;
+        if !channels.iter().any(|c| c.enabled) {
+            return;
+        }
+        if let Ok(secrets) = SecretStorage::new() {
+            let _ = secrets.load_alert_headers(&mut channels);
+        }
+        let Some(connection) = repository.get(result.connection_id) else {
+            return;
+        };
+
+        dispatcher.dispatch(
+            &channels,
+            AlertEvent {
+                connection_id: Some(connection.id),
+                name: connection.name,
+                status: result.status.clone(),
+                previous_status: Some(previous),
+                latency_ms: result.latency_ms,
+                error: result.error.clone(),
+                checked_at: result.checked_at.clone(),
+            },
+        );
+    }
+
+    fn notify<R: Runtime>(&self, app: &AppHandle<R>, result: &HealthResult, origin: CheckOrigin) {
+        let Some(repository) = app.try_state::<ConnectionRepository>() else {
+            return;
+        };
+        let Some(connection) = repository.get(result.connection_id).filter(|c| c.notify) else {
+            self.transitions
+                .lock()
+                .unwrap()
+                .forget(result.connection_id);
+            return;
+        };
+
+        let settings = repository.settings().notifications;
+        let quiet = in_quiet_hours(&settings, chrono::Local::now());
+        let transition = self.transitions.lock().unwrap().observe(
+            result,
+            origin,
+            settings.failure_threshold,
+            quiet,
+        );
+
+        if let Some(transition) = transition {
+            notify_transition(app, &connection, &transition);
+        }
+    }
+
+    // Returns the status this result replaces, or None when the result is
+    // from an earlier generation and was dropped. Read and write happen under
+    // one lock, so two results arriving together can't both see the same
+    // previous status and both alert, and a check superseded meanwhile can't
+    // slip in.
+    fn record(&self, result: HealthResult, generation: u64) -> Option<Option<HealthStatus>> {
+        let generations = self.generations.lock().unwrap();
+        if generations
+            .get(&result.connection_id)
+            .copied()
+            .unwrap_or_default()
+            != generation
+        {
+            return None;
+        }
+        let previous = self
+            .latest
+            .lock()
+            .unwrap()
+            .insert(result.connection_id, result)
+            .map(|previous| previous.status);
+        drop(generations);
+        self.results.send_replace(());
+        Some(previous)
+    }
+
+    fn generation(&self, id: Uuid) -> u64 {
+        self.generations
+            .lock()
+            .unwrap()
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn supersede(&self, id: Uuid) {
+        *self.generations.lock().unwrap().entry(id).or_default() += 1;
+    }
+
+    async fn run<R: Runtime>(&self, app: AppHandle<R>) {
+        let repository = app.state::<ConnectionRepository>();
+        let mut changes = repository.subscribe();
+
+        loop {
+            let deadline = self.dispatch_due(&app, &repository);
+
+            tokio::select! {
+                _ = sleep_until(deadline) => {}
+                _ = self.wake.notified() => {}
+                Ok(()) = changes.changed() => {}
+            }
+        }
+    }
+
+    fn dispatch_due<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        repository: &ConnectionRepository,
+    ) -> Instant {
+        // Generations are read before the connections, so a config saved in
+        // between is checked under the old generation and dropped in favour
+        // of the check that saving asks for.
+        let generations = self.generations.lock().unwrap().clone();
+        let connections = repository.all();
+        let now = Instant::now();
+        let mut next_due = self.next_due.lock().unwrap();
+
+        next_due.retain(|id, _| connections.iter().any(|c| c.id == *id));
+        self.latest
+            .lock()
+            .unwrap()
+            .retain(|id, _| connections.iter().any(|c| c.id == *id));
+
+        for connection in connections {
+            let due = next_due.entry(connection.id).or_insert(now);
+            if *due > now {
+                continue;
+            }
+
+            *due = now + check_interval(&connection);
+
+            let generation = generations.get(&connection.id).copied().unwrap_or_default();
+            let scheduler = self.clone();
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let result = check_connection_health(&connection).await;
+                scheduler.report_checked(&app, result, CheckOrigin::Scheduled, generation);
+            });
+        }
+
+        next_due
+            .values()
+            .min()
+            .copied()
+            .unwrap_or(now + Duration::from_secs(IDLE_WAKE_SECS))
+    }
+}
+
+pub fn check_interval(connection: &Connection) -> Duration {
+    let secs = connection
+        .check_interval_secs
+        .unwrap_or(DEFAULT_INTERVAL_SECS)
+        .max(MIN_INTERVAL_SECS);
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_returns_previous_status() {
+        // arrange
+        let scheduler = HealthScheduler::new();
+        let id = Uuid::new_v4();
+
+        // act
+        let first = scheduler.record(HealthResult::online(id, 10), 0);
+        let same = scheduler.record(HealthResult::online(id, 25), 0);
+        let changed = scheduler.record(HealthResult::offline(id, None), 0);
+
+        // assert
+        assert_eq!(first, Some(None));
+        assert_eq!(same, Some(Some(HealthStatus::Online)));
+        assert_eq!(changed, Some(Some(HealthStatus::Online)));
+        assert_eq!(scheduler.latest().len(), 1);
+    }
+
+    #[test]
+    fn test_record_drops_superseded_check() {
+        // arrange
+        let scheduler = HealthScheduler::new();
+        let id = Uuid::new_v4();
+        let started = scheduler.generation(id);
+        scheduler.check_soon(id);
+
+        // act
+        let stale = scheduler.record(HealthResult::offline(id, None), started);
+        let fresh = scheduler.record(HealthResult::online(id, 10), scheduler.generation(id));
+
+        // assert
+        assert_eq!(stale, None);
+        assert_eq!(fresh, Some(None));
+        assert_eq!(scheduler.latest()[0].status, HealthStatus::Online);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::models::{Connection, HealthResult, HealthStatus};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// A parsed `search_connections` query. Bare words (or "quoted phrases") must
+// all fuzzily match the name, address or tags; `tag:`, `type:`, `status:` and
+// `wol:` filters narrow the result further. Repeating a filter matches any of
+// its values.
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    pub text: Vec<String>,
+    pub tags: Vec<String>,
+    pub types: Vec<String>,
+    pub statuses: Vec<HealthStatus>,
+    pub wol: Option<bool>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = Self::default();
+
+        for token in tokenize(query) {
+            let filter = token
+                .split_once(':')
+                .map(|(key, value)| (key.to_lowercase(), value.to_lowercase()));
+            match filter {
+                Some((key, value)) if key == "tag" && !value.is_empty() => parsed.tags.push(value),
+                Some((key, value)) if key == "type" && !value.is_empty() => {
+                    parsed.types.push(value)
+                }
+                Some((key, value)) if key == "status" => match parse_status(&value) {
+                    Some(status) => parsed.statuses.push(status),
+                    None => parsed.text.push(token.to_lowercase()),
+                },
+                Some((key, value)) if key == "wol" => match value.as_str() {
+                    "true" | "yes" | "on" => parsed.wol = Some(true),
+                    "false" | "no" | "off" => parsed.wol = Some(false),
+                    _ => parsed.text.push(token.to_lowercase()),
+                },
+                _ => parsed.text.push(token.to_lowercase()),
+            }
+        }
+
+        parsed
+    }
+}
+
+// Returns the connections that match, best match first. Without any text the
+// saved order is kept.
+pub fn find_connections(
+    connections: &[Connection],
+    latest: &HashMap<Uuid, HealthResult>,
+    query: &SearchQuery,
+) -> Vec<Connection> {
+    let mut hits: Vec<(u32, &Connection)> = connections
+        .iter()
+        .filter(|c| matches_filters(c, latest.get(&c.id), query))
+        .filter_map(|c| text_score(c, &query.text).map(|score| (score, c)))
+        .collect();
+
+    hits.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| a.order.cmp(&b.order))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    hits.into_iter().map(|(_, c)| c.clone()).collect()
+}
+
+fn matches_filters(
+    connection: &Connection,
+    result: Option<&HealthResult>,
+    query: &SearchQuery,
+) -> bool {
+    let status = result.map_or(HealthStatus::Unknown, |r| r.status.clone());
+
+    (query.tags.is_empty()
+        || connection
+            .tags
+            .iter()
+            .any(|tag| query.tags.contains(&tag.to_lowercase())))
+        && (query.types.is_empty() || query.types.iter().any(|t| t == connection.config.kind()))
+        && (query.statuses.is_empty() || query.statuses.contains(&status))
+        && query
+            .wol
+            .is_none_or(|wol| wol == connection.config.wol_enabled())
+}
+
+// Every term has to match one of the fields; a connection's score is the sum
+// of each term's best field, with the name counting double.
+fn text_score(connection: &Connection, terms: &[String]) -> Option<u32> {
+    let name = connection.name.to_lowercase();
+    let address = connection.config.address().to_lowercase();
+    let tags: Vec<String> = connection.tags.iter().map(|t| t.to_lowercase()).collect();
+
+    terms.iter().try_fold(0, |total, term| {
+        let best = [
+            fuzzy_score(term, &name).map(|score| score * 2),
+            fuzzy_score(term, &address),
+            tags.iter().filter_map(|tag| fuzzy_score(term, tag)).max(),
+        ]
+        .into_iter()
+        .flatten()
+        .max()?;
+        Some(total + best)
+    })
+}
+
+// Exact > prefix > substring > in-order subsequence, with tighter and earlier
+// matches ranked higher. Both sides are expected in lowercase.
+pub fn fuzzy_score(needle: &str, haystack: &str) -> Option<u32> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    if haystack == needle {
+        return Some(1000);
+    }
+    if haystack.starts_with(needle) {
+        return Some(800);
+    }
+    if let Some(position) = haystack.find(needle) {
+        return Some(600u32.saturating_sub(position as u32).max(400));
+    }
+
+    let mut gaps = 0u32;
+    let mut chars = haystack.chars();
+    for wanted in needle.chars() {
+        loop {
+            match chars.next() {
+                Some(c) if c == wanted => break,
+                Some(_) => gaps += 1,
+                None => return None,
+            }
+        }
+    }
+    Some(300u32.saturating_sub(gaps * 10).max(1))
+}
+
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_status(value: &str) -> Option<HealthStatus> {
+    match value {
+        "online" | "up" => Some(HealthStatus::Online),
+        "offline" | "down" => Some(HealthStatus::Offline),
+        "degraded" => Some(HealthStatus::Degraded),
+        "unknown" => Some(HealthStatus::Unknown),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ConnectionConfig;
+
+    fn ssh(name: &str, host: &str, tags: &[&str], wol_enabled: bool) -> Connection {
+        Connection {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Connection::new(
+                name.into(),
+                ConnectionConfig::Ssh {
+                    host: host.into(),
+                    port: 22,
+                    username: "root".into(),
+                    wol_enabled,
+                    mac_address: None,
+                    broadcast_addr: None,
+                },
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        // arrange
+        let query = r#"tag:Prod type:ssh status:down wol:true "home lab" db status:bogus"#;
+
+        // act
+        let parsed = SearchQuery::parse(query);
+
+        // assert
+        assert_eq!(
+            parsed,
+            SearchQuery {
+                text: vec!["home lab".into(), "db".into(), "status:bogus".into()],
+                tags: vec!["prod".into()],
+                types: vec!["ssh".into()],
+                statuses: vec![HealthStatus::Offline],
+                wol: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn test_search_filters_and_ranks() {
+        // arrange
+        let nas = ssh("nas", "10.0.0.5", &["home"], true);
+        let db = ssh("prod-db", "db.internal", &["prod"], false);
+        let backup = ssh("backup server", "nas-backup.lan", &["home"], false);
+        let connections = vec![backup.clone(), db.clone(), nas.clone()];
+        let latest = HashMap::from([(db.id, HealthResult::offline(db.id, None))]);
+
+        // act
+        let by_text = find_connections(&connections, &latest, &SearchQuery::parse("nas"));
+        let fuzzy = find_connections(&connections, &latest, &SearchQuery::parse("pdb"));
+        let offline = find_connections(
+            &connections,
+            &latest,
+            &SearchQuery::parse("status:offline type:ssh"),
+        );
+        let wake = find_connections(
+            &connections,
+            &latest,
+            &SearchQuery::parse("tag:home wol:true"),
+        );
+
+        // assert
+        let names = |hits: &[Connection]| hits.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&by_text), vec!["nas", "backup server"]);
+        assert_eq!(names(&fuzzy), vec!["prod-db"]);
+        assert_eq!(names(&offline), vec!["prod-db"]);
+        assert_eq!(names(&wake), vec!["nas"]);
+    }
+}
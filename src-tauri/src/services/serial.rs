@@ -0,0 +1,337 @@
+use crate::models::{HealthResult, Parity};
+use crate::services::ping::TIMEOUT_SECS;
+use crate::services::{open_in_terminal, TerminalError};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum SerialError {
+    #[error("No serial console client found")]
+    NoClientFound,
+    #[error(transparent)]
+    Terminal(#[from] TerminalError),
+}
+
+const SERIAL_CLIENTS: &[&str] = &["picocom", "minicom", "screen"];
+
+pub fn open_serial(
+    device: &str,
+    baud_rate: u32,
+    parity: Parity,
+    stop_bits: u8,
+) -> Result<(), SerialError> {
+    let client = pick_serial_client(|client| which::which(client).is_ok(), parity, stop_bits)
+        .ok_or(SerialError::NoClientFound)?;
+
+    open_in_terminal(
+        client,
+        &serial_client_args(client, device, baud_rate, parity, stop_bits),
+    )
+    .map_err(SerialError::from)
+}
+
+// minicom takes parity and stop bits from its own setup rather than the
+// command line, so it is passed over for anything but 8N1 while another
+// client is installed.
+pub fn pick_serial_client(
+    installed: impl Fn(&str) -> bool,
+    parity: Parity,
+    stop_bits: u8,
+) -> Option<&'static str> {
+    let plain = parity == Parity::None && stop_bits == 1;
+    SERIAL_CLIENTS
+        .iter()
+        .copied()
+        .filter(|client| installed(client))
+        .min_by_key(|client| !plain && *client == "minicom")
+}
+
+pub fn serial_client_args(
+    client: &str,
+    device: &str,
+    baud_rate: u32,
+    parity: Parity,
+    stop_bits: u8,
+) -> Vec<String> {
+    match client {
+        "picocom" => {
+            let parity = match parity {
+                Parity::None => "n",
+                Parity::Even => "e",
+                Parity::Odd => "o",
+            };
+            vec![
+                "-b".to_string(),
+                baud_rate.to_string(),
+                "-y".to_string(),
+                parity.to_string(),
+                "-p".to_string(),
+                stop_bits.to_string(),
+                device.to_string(),
+            ]
+        }
+        // minicom only reads parity and stop bits from its own setup; `-o`
+        // skips the modem init string, which a console would echo back.
+        "minicom" => vec![
+            "-o".to_string(),
+            "-D".to_string(),
+            device.to_string(),
+            "-b".to_string(),
+            baud_rate.to_string(),
+        ],
+        // screen takes stty-style flags after the baud rate.
+        _ => {
+            let parity = match parity {
+                Parity::None => "-parenb",
+                Parity::Even => "parenb,-parodd",
+                Parity::Odd => "parenb,parodd",
+            };
+            let stop_bits = if stop_bits == 2 { "cstopb" } else { "-cstopb" };
+            vec![
+                device.to_string(),
+                format!("{},cs8,{},{}", baud_rate, parity, stop_bits),
+            ]
+        }
+    }
+}
+
+// Looks for the device and, with `open_check`, opens the port, but writes
+// nothing, since a console may be sitting at a prompt that would act on it.
+// The port's settings are left alone unless `keep_dtr` asks for HUPCL to be
+// cleared.
+pub async fn check_serial_health(
+    connection_id: Uuid,
+    device: &str,
+    open_check: bool,
+    keep_dtr: bool,
+) -> HealthResult {
+    let start = Instant::now();
+    let metadata = match tokio::fs::metadata(device).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return HealthResult::offline(connection_id, Some("Device not found".into()))
+        }
+        Err(e) => return HealthResult::offline(connection_id, Some(e.to_string())),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if !metadata.file_type().is_char_device() {
+            return HealthResult::offline(connection_id, Some("Not a character device".into()));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+
+    if !open_check {
+        return HealthResult::online(connection_id, start.elapsed().as_millis() as u64);
+    }
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.read(true).write(true);
+    // Without O_NONBLOCK the open waits for carrier detect on some adapters,
+    // and without O_NOCTTY the port could become the app's controlling tty.
+    #[cfg(unix)]
+    options.custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK);
+
+    let result = timeout(Duration::from_secs(TIMEOUT_SECS), options.open(device)).await;
+    let latency = start.elapsed().as_millis() as u64;
+    match result {
+        Ok(Ok(port)) => {
+            #[cfg(unix)]
+            if keep_dtr {
+                keep_dtr_on_close(&port);
+            }
+            #[cfg(not(unix))]
+            let _ = (port, keep_dtr);
+            HealthResult::online(connection_id, latency)
+        }
+        // The device is there, but a session could not be started on it.
+        Ok(Err(e)) => HealthResult::degraded(connection_id, latency, open_error(&e)),
+        Err(_) => HealthResult::degraded(
+            connection_id,
+            latency,
+            "Timed out opening the device".into(),
+        ),
+    }
+}
+
+// Opening the port raises DTR and, with HUPCL set, closing drops it again;
+// on many USB adapters that pulse resets the board. Clearing HUPCL sticks to
+// the device until it is unplugged or something sets it again, so only the
+// first check after it appears can cause a reset, but other programs using
+// the port inherit the change too.
+#[cfg(unix)]
+fn keep_dtr_on_close(port: &tokio::fs::File) {
+    use std::os::fd::AsRawFd;
+
+    let fd = port.as_raw_fd();
+    // SAFETY: `fd` is open for the duration of both calls and `termios` is a
+    // plain C struct that tcgetattr fills in before it is read.
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) == 0 && termios.c_cflag & libc::HUPCL != 0 {
+            termios.c_cflag &= !libc::HUPCL;
+            libc::tcsetattr(fd, libc::TCSANOW, &termios);
+        }
+    }
+}
+
+fn open_error(error: &std::io::Error) -> String {
+    #[cfg(unix)]
+    if error.raw_os_error() == Some(libc::EBUSY) {
+        return "Device is in use by another program".into();
+    }
+    match error.kind() {
+        ErrorKind::PermissionDenied => {
+            "Permission denied (is your user in the dialout or uucp group?)".into()
+        }
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HealthStatus;
+
+    // The master end stands in for the device and has to stay open, or the
+    // slave hangs up.
+    #[cfg(unix)]
+    fn open_pty() -> (std::os::fd::OwnedFd, String) {
+        use std::os::fd::FromRawFd;
+
+        let (mut master, mut slave) = (0, 0);
+        let mut name = [0 as libc::c_char; 128];
+        // SAFETY: every pointer is valid for the duration of the calls, and
+        // both descriptors are owned (and closed) from here on.
+        unsafe {
+            assert_eq!(
+                libc::openpty(
+                    &mut master,
+                    &mut slave,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                ),
+                0
+            );
+            assert_eq!(libc::ttyname_r(slave, name.as_mut_ptr(), name.len()), 0);
+            drop(std::os::fd::OwnedFd::from_raw_fd(slave));
+            let path = std::ffi::CStr::from_ptr(name.as_ptr()).to_string_lossy();
+            (std::os::fd::OwnedFd::from_raw_fd(master), path.into_owned())
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serial_health() {
+        // arrange
+        let (_master, device) = open_pty();
+        let regular_file = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+
+        // act
+        let present = check_serial_health(Uuid::new_v4(), &device, false, false).await;
+        let opened = check_serial_health(Uuid::new_v4(), &device, true, false).await;
+        let missing =
+            check_serial_health(Uuid::new_v4(), "/dev/ttyNOSUCHDEVICE", true, false).await;
+        let not_a_tty = check_serial_health(Uuid::new_v4(), regular_file, true, false).await;
+
+        // assert
+        assert_eq!(present.status, HealthStatus::Online);
+        assert_eq!(opened.status, HealthStatus::Online);
+        assert_eq!(missing.error.as_deref(), Some("Device not found"));
+        assert_eq!(not_a_tty.error.as_deref(), Some("Not a character device"));
+    }
+
+    #[cfg(unix)]
+    fn cflag(port: &std::fs::File) -> libc::tcflag_t {
+        use std::os::fd::AsRawFd;
+
+        // SAFETY: the descriptor stays open for the call and tcgetattr fills
+        // in the struct.
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            assert_eq!(libc::tcgetattr(port.as_raw_fd(), &mut termios), 0);
+            termios.c_cflag
+        }
+    }
+
+    // A pty starts without HUPCL, unlike most serial adapters.
+    #[cfg(unix)]
+    fn set_hupcl(port: &std::fs::File) {
+        use std::os::fd::AsRawFd;
+
+        // SAFETY: as in `cflag`; tcsetattr only reads the struct.
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            assert_eq!(libc::tcgetattr(port.as_raw_fd(), &mut termios), 0);
+            termios.c_cflag |= libc::HUPCL;
+            assert_eq!(
+                libc::tcsetattr(port.as_raw_fd(), libc::TCSANOW, &termios),
+                0
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_open_check_clears_hupcl_only_when_asked() {
+        // arrange
+        let (_master, device) = open_pty();
+        // Held open so the settings outlive the check's own open and close.
+        let slave = std::fs::File::open(&device).unwrap();
+        set_hupcl(&slave);
+        let original = cflag(&slave);
+
+        // act
+        check_serial_health(Uuid::new_v4(), &device, true, false).await;
+        let untouched = cflag(&slave);
+        check_serial_health(Uuid::new_v4(), &device, true, true).await;
+        let kept = cflag(&slave);
+
+        // assert
+        assert_eq!(untouched, original);
+        assert_ne!(untouched & libc::HUPCL, 0);
+        assert_eq!(kept & libc::HUPCL, 0);
+    }
+
+    #[test]
+    fn test_pick_serial_client_skips_minicom_for_other_framing() {
+        // arrange
+        let minicom_and_screen = |client: &str| client != "picocom";
+        let minicom_only = |client: &str| client == "minicom";
+
+        // act
+        let plain = pick_serial_client(minicom_and_screen, Parity::None, 1);
+        let even = pick_serial_client(minicom_and_screen, Parity::Even, 1);
+        let only = pick_serial_client(minicom_only, Parity::None, 2);
+
+        // assert
+        assert_eq!(plain, Some("minicom"));
+        assert_eq!(even, Some("screen"));
+        assert_eq!(only, Some("minicom"));
+    }
+
+    #[test]
+    fn test_serial_client_args() {
+        // arrange
+        let device = "/dev/ttyUSB0";
+
+        // act
+        let picocom = serial_client_args("picocom", device, 115200, Parity::Even, 2);
+        let minicom = serial_client_args("minicom", device, 9600, Parity::None, 1);
+        let screen = serial_client_args("screen", device, 9600, Parity::Odd, 1);
+
+        // assert
+        assert_eq!(
+            picocom,
+            ["-b", "115200", "-y", "e", "-p", "2", "/dev/ttyUSB0"]
+        );
+        assert_eq!(minicom, ["-o", "-D", "/dev/ttyUSB0", "-b", "9600"]);
+        assert_eq!(screen, ["/dev/ttyUSB0", "9600,cs8,parenb,parodd,-cstopb"]);
+    }
+}
@@ -0,0 +1,449 @@
+use crate::models::{
+    HealthResult, HealthThresholds, MatchFormat, PayloadFormat, ServiceProbe, Transport,
+};
+use crate::services::ping::{apply_latency_threshold, connect_tcp, resolve_addr, TIMEOUT_SECS};
+use regex::bytes::Regex;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+const MAX_REPLY_LEN: usize = 64 * 1024;
+// A UDP check with nothing to expect only waits this long for an ICMP "port
+// unreachable"; silence is all a healthy service may give back.
+const UDP_GRACE_MS: u64 = 1000;
+const SUMMARY_LEN: usize = 80;
+
+enum Expectation {
+    Pattern(Regex),
+    Bytes(Vec<u8>),
+}
+
+impl Expectation {
+    fn parse(probe: &ServiceProbe) -> Result<Option<Self>, String> {
+        let Some(expect) = probe.expect.as_deref().filter(|e| !e.is_empty()) else {
+            return Ok(None);
+        };
+        match probe.expect_format {
+            MatchFormat::Regex => Regex::new(expect)
+                .map(|regex| Some(Self::Pattern(regex)))
+                .map_err(|e| e.to_string()),
+            MatchFormat::Hex => parse_hex(expect).map(|bytes| Some(Self::Bytes(bytes))),
+        }
+    }
+
+    fn matches(&self, reply: &[u8]) -> bool {
+        match self {
+            Self::Pattern(regex) => regex.is_match(reply),
+            Self::Bytes(bytes) => reply.windows(bytes.len()).any(|window| window == bytes),
+        }
+    }
+}
+
+struct ProbeReply {
+    latency_ms: u64,
+    message: Option<String>,
+}
+
+// Without a probe this is the same TCP connect as `check_tcp_health`; with one
+// the latency is the time from sending the payload to the matching reply.
+pub async fn check_service_health(
+    connection_id: Uuid,
+    host: &str,
+    port: u16,
+    protocol: Transport,
+    probe: Option<&ServiceProbe>,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    let probe = probe.cloned().unwrap_or_default();
+    let (payload, expectation) = match (probe_payload(&probe), Expectation::parse(&probe)) {
+        (Ok(payload), Ok(expectation)) => (payload, expectation),
+        (Err(e), _) | (_, Err(e)) => {
+            return HealthResult::offline(connection_id, Some(format!("Invalid probe: {}", e)))
+        }
+    };
+
+    let reply = match protocol {
+        Transport::Tcp => probe_tcp(host, port, &payload, expectation.as_ref()).await,
+        Transport::Udp => probe_udp(host, port, &payload, expectation.as_ref()).await,
+    };
+
+    match reply {
+        Ok(reply) => {
+            let result = apply_latency_threshold(
+                HealthResult::online(connection_id, reply.latency_ms),
+                thresholds.warn_latency_ms,
+            );
+            match reply.message {
+                Some(message) => result.with_message(message),
+                None => result,
+            }
+        }
+        Err(e) => HealthResult::offline(connection_id, Some(e)),
+    }
+}
+
+async fn probe_tcp(
+    host: &str,
+    port: u16,
+    payload: &[u8],
+    expectation: Option<&Expectation>,
+) -> Result<ProbeReply, String> {
+    let (mut stream, connect_ms) = connect_tcp(host, port).await?;
+    let start = Instant::now();
+
+    let exchange = async {
+        stream.write_all(payload).await.map_err(|e| e.to_string())?;
+        let Some(expectation) = expectation else {
+            return Ok(None);
+        };
+
+        let mut reply = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.map_err(|e| e.to_string())?;
+            if n == 0 {
+                return Err(unexpected_reply(&reply));
+            }
+            reply.extend_from_slice(&buf[..n]);
+            if expectation.matches(&reply) {
+                return Ok(Some(reply));
+            }
+            if reply.len() > MAX_REPLY_LEN {
+                return Err(unexpected_reply(&reply));
+            }
+        }
+    };
+
+    match timeout(Duration::from_secs(TIMEOUT_SECS), exchange).await {
+        Ok(Ok(None)) => Ok(ProbeReply {
+            latency_ms: connect_ms,
+            message: None,
+        }),
+        Ok(Ok(Some(reply))) => Ok(ProbeReply {
+            latency_ms: start.elapsed().as_millis() as u64,
+            message: summarize_reply(&reply),
+        }),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err("No matching reply before the timeout".into()),
+    }
+}
+
+async fn probe_udp(
+    host: &str,
+    port: u16,
+    payload: &[u8],
+    expectation: Option<&Expectation>,
+) -> Result<ProbeReply, String> {
+    let addr = resolve_addr(host, port)?;
+    let local: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local).await.map_err(|e| e.to_string())?;
+    socket.connect(addr).await.map_err(|e| e.to_string())?;
+
+    let start = Instant::now();
+    socket.send(payload).await.map_err(|e| e.to_string())?;
+    let sent_ms = start.elapsed().as_millis() as u64;
+
+    let wait = match expectation {
+        Some(_) => Duration::from_secs(TIMEOUT_SECS),
+        None => Duration::from_millis(UDP_GRACE_MS),
+    };
+    let mut buf = vec![0u8; MAX_REPLY_LEN];
+    let receive = async {
+        loop {
+            // An ICMP "port unreachable" only raises the socket's error flag,
+            // which neither `recv` nor `try_recv` look at, so take it here. It
+            // reads back as "connection refused".
+            let ready = socket
+                .ready(Interest::READABLE | Interest::ERROR)
+                .await
+                .map_err(|e| e.to_string())?;
+            if ready.is_error() {
+                // Going through `try_io` clears the readiness again when the
+                // error has already been taken.
+                let pending: std::io::Result<()> =
+                    socket.try_io(Interest::ERROR, || match socket.take_error()? {
+                        Some(e) => Err(e),
+                        None => Err(ErrorKind::WouldBlock.into()),
+                    });
+                match pending {
+                    Err(e) if e.kind() != ErrorKind::WouldBlock => return Err(e.to_string()),
+                    _ => {}
+                }
+            }
+            let n = match socket.try_recv(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            if expectation.is_none_or(|expectation| expectation.matches(&buf[..n])) {
+                return Ok(buf[..n].to_vec());
+            }
+        }
+    };
+
+    match timeout(wait, receive).await {
+        Ok(Ok(reply)) => Ok(ProbeReply {
+            latency_ms: start.elapsed().as_millis() as u64,
+            message: summarize_reply(&reply),
+        }),
+        Ok(Err(e)) => Err(e),
+        Err(_) if expectation.is_none() => Ok(ProbeReply {
+            latency_ms: sent_ms,
+            message: Some("No reply (UDP cannot confirm the service is listening)".into()),
+        }),
+        Err(_) => Err("No matching reply before the timeout".into()),
+    }
+}
+
+// Run when a connection is saved, so a bad pattern is reported to whoever
+// typed it rather than as an outage.
+pub fn validate_probe(probe: &ServiceProbe) -> Result<(), String> {
+    probe_payload(probe)?;
+    Expectation::parse(probe)?;
+    Ok(())
+}
+
+pub fn probe_payload(probe: &ServiceProbe) -> Result<Vec<u8>, String> {
+    let Some(send) = probe.send.as_deref() else {
+        return Ok(Vec::new());
+    };
+    match probe.send_format {
+        PayloadFormat::Text => unescape(send),
+        PayloadFormat::Hex => parse_hex(send),
+    }
+}
+
+// Accepts "de ad be ef", "DE:AD:BE:EF", "dead-beef" or "0xdeadbeef".
+pub fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    let value = value.trim();
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    let digits: Vec<u8> = value
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace() && !matches!(b, b':' | b'-'))
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("Hex needs an even number of digits".into());
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("Invalid hex \"{}\"", String::from_utf8_lossy(pair)))
+        })
+        .collect()
+}
+
+fn unescape(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0u8; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.extend(parse_hex(&hex).map_err(|_| format!("Invalid escape \\x{}", hex))?);
+            }
+            Some(other) => return Err(format!("Unknown escape \\{}", other)),
+            None => return Err("Trailing backslash".into()),
+        }
+    }
+    Ok(bytes)
+}
+
+fn unexpected_reply(reply: &[u8]) -> String {
+    match summarize_reply(reply) {
+        Some(summary) => format!("Unexpected reply: {}", summary),
+        None => "Connection closed without a reply".into(),
+    }
+}
+
+// The first line of a text reply, or the first bytes in hex.
+fn summarize_reply(reply: &[u8]) -> Option<String> {
+    if reply.is_empty() {
+        return None;
+    }
+    match std::str::from_utf8(reply) {
+        Ok(text) => text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(|line| line.chars().take(SUMMARY_LEN).collect()),
+        Err(_) => Some(
+            reply
+                .iter()
+                .take(16)
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HealthStatus;
+    use tokio::net::TcpListener;
+
+    fn probe(send: &str, expect: &str, expect_format: MatchFormat) -> ServiceProbe {
+        ServiceProbe {
+            send: Some(send.into()),
+            send_format: PayloadFormat::Text,
+            expect: Some(expect.into()),
+            expect_format,
+        }
+    }
+
+    #[test]
+    fn test_probe_payload_formats() {
+        // arrange
+        let text = probe(r"PING\r\n\x00", "", MatchFormat::Regex);
+        let hex = ServiceProbe {
+            send_format: PayloadFormat::Hex,
+            ..probe("0x10 0c:00-04", "", MatchFormat::Regex)
+        };
+
+        // act
+        let text_payload = probe_payload(&text);
+        let hex_payload = probe_payload(&hex);
+
+        // assert
+        assert_eq!(text_payload.unwrap(), b"PING\r\n\0");
+        assert_eq!(hex_payload.unwrap(), [0x10, 0x0c, 0x00, 0x04]);
+        assert!(parse_hex("abc").is_err());
+        assert!(unescape(r"\q").is_err());
+    }
+
+    #[test]
+    fn test_validate_probe() {
+        // arrange
+        let valid = probe("PING", "^\\+PONG", MatchFormat::Regex);
+        let bad_regex = probe("PING", "(unclosed", MatchFormat::Regex);
+        let bad_hex = probe("PING", "abc", MatchFormat::Hex);
+
+        // act
+        let results = [&valid, &bad_regex, &bad_hex].map(validate_probe);
+
+        // assert
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe_matches_reply() {
+        // arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 6];
+                stream.read_exact(&mut request).await.unwrap();
+                if &request == b"PING\r\n" {
+                    stream.write_all(b"+PONG v1.2\r\n").await.unwrap();
+                }
+            }
+        });
+        let id = Uuid::new_v4();
+        let thresholds = HealthThresholds::default();
+        let matching = probe(r"PING\r\n", r"^\+PONG", MatchFormat::Regex);
+        let mismatched = probe(r"PING\r\n", "^HELLO", MatchFormat::Regex);
+
+        // act
+        let online = check_service_health(
+            id,
+            "127.0.0.1",
+            port,
+            Transport::Tcp,
+            Some(&matching),
+            &thresholds,
+        )
+        .await;
+        let offline = check_service_health(
+            id,
+            "127.0.0.1",
+            port,
+            Transport::Tcp,
+            Some(&mismatched),
+            &thresholds,
+        )
+        .await;
+
+        // assert
+        assert_eq!(online.status, HealthStatus::Online);
+        assert_eq!(online.message.as_deref(), Some("+PONG v1.2"));
+        assert_eq!(offline.status, HealthStatus::Offline);
+        assert_eq!(
+            offline.error.as_deref(),
+            Some("Unexpected reply: +PONG v1.2")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_udp_probe() {
+        // arrange
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (_, peer) = server.recv_from(&mut buf).await.unwrap();
+            server
+                .send_to(&[0x00, 0xca, 0xfe, 0x01], peer)
+                .await
+                .unwrap();
+        });
+        let closed_port = {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.local_addr().unwrap().port()
+        };
+        let id = Uuid::new_v4();
+        let thresholds = HealthThresholds::default();
+        let expect_cafe = probe("status", "ca fe", MatchFormat::Hex);
+
+        // act
+        let online = check_service_health(
+            id,
+            "127.0.0.1",
+            port,
+            Transport::Udp,
+            Some(&expect_cafe),
+            &thresholds,
+        )
+        .await;
+        let refused = check_service_health(
+            id,
+            "127.0.0.1",
+            closed_port,
+            Transport::Udp,
+            None,
+            &thresholds,
+        )
+        .await;
+
+        // assert
+        assert_eq!(online.status, HealthStatus::Online);
+        assert_eq!(online.message.as_deref(), Some("00 ca fe 01"));
+        assert_eq!(refused.status, HealthStatus::Offline);
+    }
+}
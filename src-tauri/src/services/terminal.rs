@@ -11,38 +11,34 @@ pub enum TerminalError {
 
 #[cfg(target_os = "linux")]
 const LINUX_TERMINALS: &[(&str, &[&str])] = &[
-    ("gnome-terminal", &["--", "ssh"]),
-    ("konsole", &["-e", "ssh"]),
-    ("alacritty", &["-e", "ssh"]),
-    ("kitty", &["ssh"]),
-    ("xterm", &["-e", "ssh"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("xterm", &["-e"]),
 ];
 
 pub fn open_ssh_in_terminal(username: &str, host: &str, port: u16) -> Result<(), TerminalError> {
-    let ssh_target = if port == 22 {
-        format!("{}@{}", username, host)
-    } else {
-        format!("{}@{}", username, host)
-    };
-
-    let port_args: Vec<String> = if port != 22 {
+    let mut args: Vec<String> = if port != 22 {
         vec!["-p".to_string(), port.to_string()]
     } else {
         vec![]
     };
+    args.push(format!("{}@{}", username, host));
+
+    open_in_terminal("ssh", &args)
+}
 
+// Runs `program` in the first terminal found, or in Terminal.app on macOS.
+pub fn open_in_terminal(program: &str, args: &[String]) -> Result<(), TerminalError> {
     #[cfg(target_os = "linux")]
     {
         for (terminal, base_args) in LINUX_TERMINALS {
             if which::which(terminal).is_ok() {
-                let mut args: Vec<&str> = base_args.to_vec();
-
-                let port_arg_refs: Vec<&str> = port_args.iter().map(|s| s.as_str()).collect();
-                args.extend(port_arg_refs.iter());
-                args.push(&ssh_target);
-
                 Command::new(terminal)
-                    .args(&args)
+                    .args(base_args.iter())
+                    .arg(program)
+                    .args(args)
                     .spawn()
                     .map_err(|e| TerminalError::LaunchFailed(e.to_string()))?;
 
@@ -54,18 +50,17 @@ pub fn open_ssh_in_terminal(username: &str, host: &str, port: u16) -> Result<(),
 
     #[cfg(target_os = "macos")]
     {
-        let ssh_cmd = if port != 22 {
-            format!("ssh -p {} {}", port, ssh_target)
-        } else {
-            format!("ssh {}", ssh_target)
-        };
+        let command = std::iter::once(program.to_string())
+            .chain(args.iter().map(|arg| shell_quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
 
         let script = format!(
             r#"tell application "Terminal"
                 activate
                 do script "{}"
             end tell"#,
-            ssh_cmd
+            command.replace('\\', "\\\\").replace('"', "\\\"")
         );
 
         Command::new("osascript")
@@ -78,6 +73,111 @@ pub fn open_ssh_in_terminal(username: &str, host: &str, port: u16) -> Result<(),
 
     #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     {
+        let _ = (program, args);
         Err(TerminalError::NoTerminalFound)
     }
 }
+
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:@=,+%".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+pub fn psql_args(
+    host: &str,
+    port: u16,
+    username: Option<&str>,
+    database: Option<&str>,
+) -> Vec<String> {
+    let mut args = vec![
+        "-h".to_string(),
+        host.to_string(),
+        "-p".to_string(),
+        port.to_string(),
+    ];
+    if let Some(username) = username {
+        args.extend(["-U".to_string(), username.to_string()]);
+    }
+    if let Some(database) = database {
+        args.extend(["-d".to_string(), database.to_string()]);
+    }
+    args
+}
+
+pub fn mysql_args(
+    host: &str,
+    port: u16,
+    username: Option<&str>,
+    database: Option<&str>,
+) -> Vec<String> {
+    let mut args = vec![
+        "-h".to_string(),
+        host.to_string(),
+        "-P".to_string(),
+        port.to_string(),
+    ];
+    // Unlike psql, mysql does not ask for a password unless told to.
+    if let Some(username) = username {
+        args.extend(["-u".to_string(), username.to_string(), "-p".to_string()]);
+    }
+    if let Some(database) = database {
+        args.push(database.to_string());
+    }
+    args
+}
+
+pub fn redis_cli_args(host: &str, port: u16, database: Option<u32>) -> Vec<String> {
+    let mut args = vec![
+        "-h".to_string(),
+        host.to_string(),
+        "-p".to_string(),
+        port.to_string(),
+    ];
+    if let Some(database) = database {
+        args.extend(["-n".to_string(), database.to_string()]);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_cli_args() {
+        // arrange
+        let host = "db.lab";
+
+        // act
+        let psql = psql_args(host, 5433, Some("app"), Some("orders"));
+        let mysql = mysql_args(host, 3306, Some("root"), None);
+        let redis = redis_cli_args(host, 6379, Some(2));
+
+        // assert
+        assert_eq!(
+            psql,
+            ["-h", "db.lab", "-p", "5433", "-U", "app", "-d", "orders"]
+        );
+        assert_eq!(mysql, ["-h", "db.lab", "-P", "3306", "-u", "root", "-p"]);
+        assert_eq!(redis, ["-h", "db.lab", "-p", "6379", "-n", "2"]);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        // arrange
+        let args = ["db.lab", "my db", "it's", ""];
+
+        // act
+        let quoted: Vec<String> = args.iter().map(|arg| shell_quote(arg)).collect();
+
+        // assert
+        assert_eq!(quoted, ["db.lab", "'my db'", r"'it'\''s'", "''"]);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::models::{HealthResult, HealthThresholds, TlsCertificate, TlsInfo};
+use crate::services::ClientIdentity;
+use chrono::{DateTime, Utc};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{
+    ClientSessionStore, Resumption, Tls12ClientSessionValue, Tls13ClientSessionValue,
+    WebPkiServerVerifier,
+};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, NamedGroup, RootCertStore, SignatureScheme};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use thiserror::Error;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+#[derive(Error, Debug)]
+pub enum TlsError {
+    #[error("Invalid server name: {0}")]
+    InvalidServerName(String),
+    #[error("TLS connection failed: {0}")]
+    ConnectFailed(String),
+    #[error("Server sent no certificate")]
+    NoCertificate,
+    #[error("Could not parse certificate: {0}")]
+    InvalidCertificate(String),
+}
+
+type RecordedChain = (Vec<CertificateDer<'static>>, Option<String>);
+
+// Runs the normal WebPKI verification for the probed host and records its
+// verdict, so we can report on certificates we don't trust. Unless strict,
+// an untrusted certificate doesn't fail the handshake. Any other host (an
+// HTTPS proxy, say) is verified as usual and left out of the record.
+#[derive(Debug)]
+pub struct TlsRecorder {
+    inner: Arc<WebPkiServerVerifier>,
+    host: ServerName<'static>,
+    strict: bool,
+    started: OnceLock<Instant>,
+    recorded: Mutex<Option<RecordedChain>>,
+}
+
+impl TlsRecorder {
+    // When the handshake with the probed host began, which is when its TCP
+    // connection (or the tunnel through a proxy) was ready.
+    pub fn started(&self) -> Option<Instant> {
+        self.started.get().copied()
+    }
+
+    pub fn validation_error(&self) -> Option<String> {
+        self.recorded
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|(_, error)| error.clone())
+    }
+
+    pub fn take_info(&self) -> Result<Option<TlsInfo>, TlsError> {
+        match self.recorded.lock().unwrap().take() {
+            Some((chain, validation_error)) => {
+                build_tls_info(&chain, validation_error, Utc::now()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl ServerCertVerifier for TlsRecorder {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verdict = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        );
+        if *server_name != self.host {
+            return verdict;
+        }
+
+        let chain = std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|cert| cert.clone().into_owned())
+            .collect();
+        let error = verdict.as_ref().err().map(|e| e.to_string());
+        *self.recorded.lock().unwrap() = Some((chain, error));
+
+        if self.strict {
+            verdict
+        } else {
+            Ok(ServerCertVerified::assertion())
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// Standing in as the session store is how the start of the handshake gets
+// timed: rustls asks it for a key exchange hint while building the
+// ClientHello. Nothing is kept, so every probe makes a full handshake.
+impl ClientSessionStore for TlsRecorder {
+    fn set_kx_hint(&self, _: ServerName<'static>, _: NamedGroup) {}
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        if *server_name == self.host {
+            let _ = self.started.set(Instant::now());
+        }
+        None
+    }
+
+    fn set_tls12_session(&self, _: ServerName<'static>, _: Tls12ClientSessionValue) {}
+
+    fn tls12_session(&self, _: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        None
+    }
+
+    fn remove_tls12_session(&self, _: &ServerName<'static>) {}
+
+    fn insert_tls13_ticket(&self, _: ServerName<'static>, _: Tls13ClientSessionValue) {}
+
+    fn take_tls13_ticket(&self, _: &ServerName<'static>) -> Option<Tls13ClientSessionValue> {
+        None
+    }
+}
+
+// A client config for probing `host` that records its certificate chain and
+// handshake start in the returned recorder.
+pub fn probe_tls_config(
+    host: &str,
+    strict: bool,
+    identity: Option<&ClientIdentity>,
+) -> Result<(rustls::ClientConfig, Arc<TlsRecorder>), TlsError> {
+    let (provider, inner) = webpki_verifier()?;
+    let host = ServerName::try_from(host.to_string())
+        .map_err(|_| TlsError::InvalidServerName(host.to_string()))?;
+    let recorder = Arc::new(TlsRecorder {
+        inner,
+        host,
+        strict,
+        started: OnceLock::new(),
+        recorded: Mutex::new(None),
+    });
+
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::ConnectFailed(e.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(recorder.clone());
+    let mut config = match identity {
+        Some(identity) => builder
+            .with_client_auth_cert(identity.certs.clone(), identity.key.clone_key())
+            .map_err(|e| TlsError::ConnectFailed(e.to_string()))?,
+        None => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    config.resumption = Resumption::store(recorder.clone());
+
+    Ok((config, recorder))
+}
+
+type SharedVerifier = (Arc<CryptoProvider>, Arc<WebPkiServerVerifier>);
+
+// Building the root store is the expensive part of a handshake, and the roots
+// don't change while the app runs, so it is done once. The bundled Mozilla
+// roots are joined by the system's, so sites signed by a private CA that the
+// machine trusts check out as valid.
+fn webpki_verifier() -> Result<SharedVerifier, TlsError> {
+    static VERIFIER: OnceLock<Result<SharedVerifier, String>> = OnceLock::new();
+
+    VERIFIER
+        .get_or_init(|| {
+            let provider = Arc::new(ring::default_provider());
+            let mut roots = RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            // Certificates the system store can't hand over are skipped; the
+            // bundled roots still apply.
+            roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map(|verifier| (provider, verifier))
+                .map_err(|e| e.to_string())
+        })
+        .clone()
+        .map_err(TlsError::ConnectFailed)
+}
+
+fn build_tls_info(
+    chain: &[CertificateDer<'_>],
+    validation_error: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<TlsInfo, TlsError> {
+    let leaf_der = chain.first().ok_or(TlsError::NoCertificate)?;
+    let (_, leaf) = X509Certificate::from_der(leaf_der)
+        .map_err(|e| TlsError::InvalidCertificate(e.to_string()))?;
+
+    let subject_alt_names = match leaf.subject_alternative_name() {
+        Ok(Some(san)) => san
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(dns) => Some(dns.to_string()),
+                GeneralName::IPAddress(bytes) => ip_from_bytes(bytes),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let certificates = chain
+        .iter()
+        .filter_map(|der| X509Certificate::from_der(der).ok())
+        .map(|(_, cert)| TlsCertificate {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            not_before: timestamp(cert.validity().not_before.timestamp()),
+            not_after: timestamp(cert.validity().not_after.timestamp()),
+        })
+        .collect();
+
+    let not_after = timestamp(leaf.validity().not_after.timestamp());
+
+    Ok(TlsInfo {
+        chain: certificates,
+        issuer: leaf.issuer().to_string(),
+        subject_alt_names,
+        days_until_expiry: (not_after - now).num_days(),
+        validation_error,
+    })
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes)
+            .ok()
+            .map(|b| std::net::Ipv4Addr::from(b).to_string()),
+        16 => <[u8; 16]>::try_from(bytes)
+            .ok()
+            .map(|b| std::net::Ipv6Addr::from(b).to_string()),
+        _ => None,
+    }
+}
+
+pub fn apply_tls_policy(
+    result: HealthResult,
+    info: TlsInfo,
+    thresholds: &HealthThresholds,
+) -> HealthResult {
+    let warn_days = i64::from(thresholds.cert_expiry_warn_days);
+    let mut reasons = Vec::new();
+    if let Some(error) = &info.validation_error {
+        reasons.push(format!("Certificate invalid: {}", error));
+    } else if info.days_until_expiry < 0 {
+        reasons.push("Certificate expired".into());
+    } else if info.days_until_expiry <= warn_days {
+        reasons.push(format!(
+            "Certificate expires in {} days",
+            info.days_until_expiry
+        ));
+    }
+
+    // An intermediate running out breaks the chain just like the leaf does.
+    // One that already has fails validation, which is reported above.
+    let now = Utc::now();
+    for cert in info.chain.iter().skip(1) {
+        let days = (cert.not_after - now).num_days();
+        if (0..=warn_days).contains(&days) {
+            reasons.push(format!(
+                "Intermediate certificate {} expires in {} days",
+                cert.subject, days
+            ));
+        }
+    }
+
+    reasons
+        .into_iter()
+        .fold(result, HealthResult::degrade)
+        .with_tls(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HealthStatus;
+
+    fn tls_info(days_until_expiry: i64, validation_error: Option<String>) -> TlsInfo {
+        TlsInfo {
+            chain: Vec::new(),
+            issuer: "CN=Test CA".into(),
+            subject_alt_names: vec!["example.com".into()],
+            days_until_expiry,
+            validation_error,
+        }
+    }
+
+    #[test]
+    fn test_apply_tls_policy_expiring_soon() {
+        // arrange
+        let result = HealthResult::online(uuid::Uuid::new_v4(), 20);
+        let thresholds = HealthThresholds::default();
+
+        // act
+        let result = apply_tls_policy(result, tls_info(3, None), &thresholds);
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Degraded);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("Certificate expires in 3 days")
+        );
+        assert!(result.tls.is_some());
+    }
+
+    #[test]
+    fn test_apply_tls_policy_invalid_chain() {
+        // arrange
+        let result = HealthResult::online(uuid::Uuid::new_v4(), 20);
+        let thresholds = HealthThresholds::default();
+        let info = tls_info(300, Some("invalid peer certificate: UnknownIssuer".into()));
+
+        // act
+        let result = apply_tls_policy(result, info, &thresholds);
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Degraded);
+    }
+
+    #[test]
+    fn test_apply_tls_policy_expiring_intermediate() {
+        // arrange
+        let result = HealthResult::degraded(uuid::Uuid::new_v4(), 20, "HTTP 404".into());
+        let thresholds = HealthThresholds::default();
+        let certificate = |subject: &str, days| TlsCertificate {
+            subject: subject.into(),
+            issuer: "CN=Test Root".into(),
+            not_before: Utc::now() - chrono::Duration::days(300),
+            not_after: Utc::now() + chrono::Duration::days(days) + chrono::Duration::hours(1),
+        };
+        let info = TlsInfo {
+            chain: vec![
+                certificate("CN=example.com", 90),
+                certificate("CN=Test CA", 5),
+            ],
+            ..tls_info(90, None)
+        };
+
+        // act
+        let result = apply_tls_policy(result, info, &thresholds);
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Degraded);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("HTTP 404; Intermediate certificate CN=Test CA expires in 5 days")
+        );
+    }
+
+    #[test]
+    fn test_apply_tls_policy_healthy() {
+        // arrange
+        let result = HealthResult::online(uuid::Uuid::new_v4(), 20);
+        let thresholds = HealthThresholds::default();
+
+        // act
+        let result = apply_tls_policy(result, tls_info(90, None), &thresholds);
+
+        // assert
+        assert_eq!(result.status, HealthStatus::Online);
+    }
+}
@@ -12,6 +12,12 @@ pub enum WolError {
     PacketCreation(String),
     #[error("Failed to send packet: {0}")]
     SendFailed(#[from] std::io::Error),
+    #[error("Wake-on-LAN is not enabled for this connection")]
+    NotEnabled,
+    #[error("No MAC address configured")]
+    NoMacAddress,
+    #[error("Wake-on-LAN is only supported for SSH connections")]
+    Unsupported,
 }
 
 pub fn send_wol_packet(mac_address: &str, broadcast_addr: Option<&str>) -> Result<(), WolError> {
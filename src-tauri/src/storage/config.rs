@@ -1,10 +1,20 @@
-use crate::models::Connection;
-use chrono::Utc;
+use crate::models::{AppSettings, ConfigBackup, Connection, Group};
+use crate::storage::migrations::{document_version, migrate, CURRENT_VERSION};
+use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
-use std::fs;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
 use std::path::PathBuf;
 use thiserror::Error;
-use uuid::Uuid;
+
+const MAX_BACKUPS: usize = 10;
+// A burst of edits (dragging cards around, say) leaves one backup from before
+// it rather than pushing every older one out of the rotation.
+const BACKUP_INTERVAL_SECS: i64 = 5 * 60;
+const BACKUP_PREFIX: &str = "connections-";
+const MIGRATION_BACKUP_PREFIX: &str = "pre-migration-";
 
 #[derive(Error, Debug)]
 pub enum StorageError {
@@ -14,94 +24,470 @@ pub enum StorageError {
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Secrets file could not be decrypted")]
+    Decrypt,
+    #[error("The secrets key is missing, so the stored credentials cannot be read")]
+    SecretsKeyMissing,
+    #[error("Backup not found: {0}")]
+    BackupNotFound(String),
+    #[error("connections.json is version {0}, which is newer than this app supports")]
+    UnsupportedVersion(u32),
+    #[error("connections.json could not be loaded; restore a backup to make changes")]
+    LoadFailed(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigDocument {
+    pub version: u32,
+    pub connections: Vec<Connection>,
+    pub groups: Vec<Group>,
+    pub settings: AppSettings,
+    // Entries this build cannot parse (a type from a newer version, or a
+    // hand edit gone wrong). They are written back untouched.
+    #[serde(skip)]
+    pub unparsed: Vec<Value>,
+}
+
+impl Default for ConfigDocument {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            connections: Vec::new(),
+            groups: Vec::new(),
+            settings: AppSettings::default(),
+            unparsed: Vec::new(),
+        }
+    }
 }
 
 pub struct ConfigStorage {
     config_path: PathBuf,
+    backup_interval_secs: i64,
+}
+
+pub(crate) fn config_dir() -> Result<PathBuf, StorageError> {
+    let project_dirs =
+        ProjectDirs::from("com", "turbowaffle", "turbo-waffle").ok_or(StorageError::NoConfigDir)?;
+    let config_dir = project_dirs.config_dir();
+
+    fs::create_dir_all(config_dir)?;
+
+    Ok(config_dir.to_path_buf())
 }
 
 impl ConfigStorage {
     pub fn new() -> Result<Self, StorageError> {
-        let project_dirs =
-            ProjectDirs::from("com", "turbowaffle", "turbo-waffle").ok_or(StorageError::NoConfigDir)?;
-        let config_dir = project_dirs.config_dir();
+        Ok(Self::with_path(config_dir()?.join("connections.json")))
+    }
 
-        fs::create_dir_all(config_dir)?;
+    pub fn with_path(config_path: PathBuf) -> Self {
+        Self {
+            config_path,
+            backup_interval_secs: BACKUP_INTERVAL_SECS,
+        }
+    }
 
-        let config_path = config_dir.join("connections.json");
+    pub fn load_connections(&self) -> Result<Vec<Connection>, StorageError> {
+        Ok(self.load_document()?.connections)
+    }
 
-        Ok(Self { config_path })
+    pub fn load_document(&self) -> Result<ConfigDocument, StorageError> {
+        let (document, migrated) = self.read_document()?;
+        if migrated {
+            let _lock = self.lock()?;
+            self.write_document(&document)?;
+        }
+        Ok(document)
     }
 
-    pub fn load_connections(&self) -> Result<Vec<Connection>, StorageError> {
-        if !self.config_path.exists() {
+    pub fn save_connections(&self, connections: &[Connection]) -> Result<(), StorageError> {
+        self.update(|stored| *stored = connections.to_vec())
+    }
+
+    // Holds the config lock across the whole load-modify-save so concurrent
+    // commands (or a second instance) can't overwrite each other's edits.
+    pub fn update<T>(
+        &self,
+        modify: impl FnOnce(&mut Vec<Connection>) -> T,
+    ) -> Result<T, StorageError> {
+        self.update_document(|document| modify(&mut document.connections))
+    }
+
+    pub fn update_document<T>(
+        &self,
+        modify: impl FnOnce(&mut ConfigDocument) -> T,
+    ) -> Result<T, StorageError> {
+        let _lock = self.lock()?;
+        let (mut document, _) = self.read_document()?;
+        let result = modify(&mut document);
+        self.write_document(&document)?;
+        Ok(result)
+    }
+
+    pub fn save_settings(&self, settings: &AppSettings) -> Result<(), StorageError> {
+        self.update_document(|document| document.settings = settings.clone())
+    }
+
+    pub fn save_connection(&self, connection: Connection) -> Result<(), StorageError> {
+        self.update(|connections| {
+            if let Some(idx) = connections.iter().position(|c| c.id == connection.id) {
+                connections[idx] = connection;
+            } else {
+                connections.push(connection);
+            }
+        })
+    }
+
+    pub fn list_backups(&self) -> Result<Vec<ConfigBackup>, StorageError> {
+        let dir = self.backup_dir();
+        if !dir.exists() {
             return Ok(Vec::new());
         }
 
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !is_backup_name(&name) {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            backups.push(ConfigBackup {
+                name,
+                created_at: metadata
+                    .modified()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_else(|_| Utc::now()),
+                size_bytes: metadata.len(),
+            });
+        }
+
+        // Newest first; names embed a sortable timestamp for copies made
+        // within the same tick.
+        backups.sort_by(|a, b| (b.created_at, &b.name).cmp(&(a.created_at, &a.name)));
+        Ok(backups)
+    }
+
+    pub fn restore_backup(&self, name: &str) -> Result<ConfigDocument, StorageError> {
+        let path = self.backup_dir().join(name);
+        if !is_backup_name(name) || !path.is_file() {
+            return Err(StorageError::BackupNotFound(name.to_string()));
+        }
+
+        let (document, _) = self.parse_document(&fs::read_to_string(path)?)?;
+        let _lock = self.lock()?;
+        // Whatever is being replaced is always kept, however recent the last
+        // backup is.
+        self.rotate_backups(true)?;
+        self.write_document(&document)?;
+        Ok(document)
+    }
+
+    // Returns the document upgraded to the current version, and whether the
+    // file on disk still needs rewriting in the new format.
+    fn read_document(&self) -> Result<(ConfigDocument, bool), StorageError> {
+        if !self.config_path.exists() {
+            return Ok((ConfigDocument::default(), false));
+        }
+
         let contents = fs::read_to_string(&self.config_path)?;
+        let (document, version) = self.parse_document(&contents)?;
+        if version < CURRENT_VERSION {
+            self.backup_before_migration(version)?;
+        }
+        Ok((document, version < CURRENT_VERSION))
+    }
+
+    fn parse_document(&self, contents: &str) -> Result<(ConfigDocument, u32), StorageError> {
+        let value: Value = serde_json::from_str(contents)?;
+        let version = document_version(&value);
+        if version > CURRENT_VERSION {
+            return Err(StorageError::UnsupportedVersion(version));
+        }
 
-        match serde_json::from_str::<Vec<Connection>>(&contents) {
-            Ok(connections) => Ok(connections),
-            Err(_) => {
-                let values: Vec<serde_json::Value> = serde_json::from_str(&contents)?;
-                let mut connections = Vec::new();
-                let mut invalid = Vec::new();
-
-                for value in values {
-                    match serde_json::from_value::<Connection>(value.clone()) {
-                        Ok(connection) => connections.push(connection),
-                        Err(_) => invalid.push(value),
-                    }
-                }
-
-                if !invalid.is_empty() {
-                    let backup_path = self.config_path.with_file_name(format!(
-                        "connections.invalid-{}.json",
-                        Utc::now().format("%Y%m%d%H%M%S")
-                    ));
-                    if let Ok(contents) = serde_json::to_string_pretty(&invalid) {
-                        let _ = fs::write(backup_path, contents);
-                    }
-                }
-
-                Ok(connections)
+        let mut value = migrate(value, version);
+        let settings = value
+            .get_mut("settings")
+            .map(Value::take)
+            .and_then(|settings| serde_json::from_value(settings).ok())
+            .unwrap_or_default();
+        let groups = value
+            .get_mut("groups")
+            .map(Value::take)
+            .and_then(|groups| serde_json::from_value(groups).ok())
+            .unwrap_or_default();
+        let entries = match value.get_mut("connections").map(Value::take) {
+            Some(Value::Array(entries)) => entries,
+            _ => Vec::new(),
+        };
+
+        let mut connections = Vec::new();
+        let mut unparsed = Vec::new();
+        for entry in entries {
+            match serde_json::from_value::<Connection>(entry.clone()) {
+                Ok(connection) => connections.push(connection),
+                Err(_) => unparsed.push(entry),
             }
         }
+
+        let document = ConfigDocument {
+            version: CURRENT_VERSION,
+            connections,
+            groups,
+            settings,
+            unparsed,
+        };
+        Ok((document, version))
     }
 
-    pub fn save_connections(&self, connections: &[Connection]) -> Result<(), StorageError> {
-        let contents = serde_json::to_string_pretty(connections)?;
-        fs::write(&self.config_path, contents)?;
+    fn lock(&self) -> Result<File, StorageError> {
+        // The lock lives in its own file because the config itself is
+        // replaced by rename on every write.
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.config_path.with_extension("json.lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    fn write_document(&self, document: &ConfigDocument) -> Result<(), StorageError> {
+        let mut value = serde_json::to_value(document)?;
+        if let Some(Value::Array(connections)) = value.get_mut("connections") {
+            connections.extend(document.unparsed.iter().cloned());
+        }
+        let contents = serde_json::to_string_pretty(&value)?;
+        self.rotate_backups(false)?;
+
+        let temp_path = self.config_path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.config_path)?;
         Ok(())
     }
 
-    pub fn get_connection(&self, id: Uuid) -> Result<Option<Connection>, StorageError> {
-        let connections = self.load_connections()?;
-        Ok(connections.into_iter().find(|c| c.id == id))
+    fn backup_dir(&self) -> PathBuf {
+        self.config_path.with_file_name("backups")
     }
 
-    pub fn save_connection(&self, connection: Connection) -> Result<(), StorageError> {
-        let mut connections = self.load_connections()?;
+    // Kept outside the rotation so an upgrade can always be undone by hand.
+    fn backup_before_migration(&self, version: u32) -> Result<(), StorageError> {
+        let dir = self.backup_dir();
+        fs::create_dir_all(&dir)?;
+        let name = format!(
+            "pre-migration-v{}-{}.json",
+            version,
+            Utc::now().format("%Y%m%d-%H%M%S")
+        );
+        fs::copy(&self.config_path, dir.join(name))?;
+        Ok(())
+    }
+
+    fn rotate_backups(&self, force: bool) -> Result<(), StorageError> {
+        if !self.config_path.exists() {
+            return Ok(());
+        }
+
+        let dir = self.backup_dir();
+        fs::create_dir_all(&dir)?;
+        let current = fs::read(&self.config_path)?;
+        if let Some(newest) = self.rotated_backups()?.first() {
+            let unchanged = fs::read(dir.join(&newest.name)).is_ok_and(|saved| saved == current);
+            let age = Utc::now().signed_duration_since(newest.created_at);
+            if unchanged || (!force && age.num_seconds() < self.backup_interval_secs) {
+                return Ok(());
+            }
+        }
+
+        let name = format!(
+            "{}{}.json",
+            BACKUP_PREFIX,
+            Utc::now().format("%Y%m%d-%H%M%S%6f")
+        );
+        fs::write(dir.join(name), current)?;
+
+        for stale in self.rotated_backups()?.iter().skip(MAX_BACKUPS) {
+            let _ = fs::remove_file(dir.join(&stale.name));
+        }
+        Ok(())
+    }
+
+    // The copies taken on writes, newest first; pre-migration ones are not
+    // rotated out.
+    fn rotated_backups(&self) -> Result<Vec<ConfigBackup>, StorageError> {
+        let mut backups: Vec<_> = self
+            .list_backups()?
+            .into_iter()
+            .filter(|backup| backup.name.starts_with(BACKUP_PREFIX))
+            .collect();
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(backups)
+    }
+}
 
-        if let Some(idx) = connections.iter().position(|c| c.id == connection.id) {
-            connections[idx] = connection;
-        } else {
-            connections.push(connection);
+fn is_backup_name(name: &str) -> bool {
+    (name.starts_with(BACKUP_PREFIX) || name.starts_with(MIGRATION_BACKUP_PREFIX))
+        && name.ends_with(".json")
+        && !name.contains(['/', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::{connection, TempDir};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn temp_storage() -> (ConfigStorage, TempDir) {
+        let dir = TempDir::new("config");
+        let mut storage = ConfigStorage::with_path(dir.join("connections.json"));
+        storage.backup_interval_secs = 0;
+        (storage, dir)
+    }
+
+    #[test]
+    fn test_concurrent_saves_keep_every_connection() {
+        // arrange
+        let (storage, _dir) = temp_storage();
+        let storage = Arc::new(storage);
+
+        // act
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                thread::spawn(move || {
+                    storage
+                        .save_connection(connection(&format!("host-{}", i)))
+                        .unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
         }
 
-        self.save_connections(&connections)
+        // assert
+        assert_eq!(storage.load_connections().unwrap().len(), 8);
     }
 
-    pub fn delete_connection(&self, id: Uuid) -> Result<bool, StorageError> {
-        let mut connections = self.load_connections()?;
-        let original_len = connections.len();
-        connections.retain(|c| c.id != id);
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        // arrange
+        let (storage, _dir) = temp_storage();
+        for i in 0..MAX_BACKUPS + 3 {
+            storage
+                .save_connection(connection(&format!("extra-{}", i)))
+                .unwrap();
+        }
+
+        // act
+        let backups = storage.list_backups().unwrap();
+        let oldest = backups.last().unwrap().name.clone();
+        let restored = storage.restore_backup(&oldest).unwrap().connections;
+
+        // assert
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert_eq!(restored.len(), storage.load_connections().unwrap().len());
+        assert!(restored.len() < MAX_BACKUPS + 3);
+        assert!(storage.restore_backup("../connections.json").is_err());
+    }
 
-        if connections.len() != original_len {
-            self.save_connections(&connections)?;
-            Ok(true)
-        } else {
-            Ok(false)
+    #[test]
+    fn test_backups_skip_bursts_but_not_restores() {
+        // arrange
+        let (mut storage, _dir) = temp_storage();
+        storage.backup_interval_secs = BACKUP_INTERVAL_SECS;
+        for i in 0..5 {
+            storage
+                .save_connection(connection(&format!("burst-{}", i)))
+                .unwrap();
         }
+        let before_restore = storage.list_backups().unwrap();
+
+        // act
+        storage.restore_backup(&before_restore[0].name).unwrap();
+        let after_restore = storage.list_backups().unwrap();
+        storage.backup_interval_secs = 0;
+        let unchanged = connection("unchanged");
+        storage.save_connection(unchanged.clone()).unwrap();
+        storage.save_connection(unchanged.clone()).unwrap();
+        storage.save_connection(unchanged).unwrap();
+
+        // assert
+        assert_eq!(before_restore.len(), 1);
+        assert_eq!(after_restore.len(), 2);
+        // The third save finds the file identical to the newest backup.
+        assert_eq!(storage.list_backups().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_migrates_bare_array_with_backup() {
+        // arrange
+        let (storage, dir) = temp_storage();
+        let legacy = serde_json::to_string(&vec![connection("legacy")]).unwrap();
+        fs::write(dir.join("connections.json"), legacy).unwrap();
+
+        // act
+        let connections = storage.load_connections().unwrap();
+        let rewritten: Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("connections.json")).unwrap())
+                .unwrap();
+        let backup = storage
+            .list_backups()
+            .unwrap()
+            .into_iter()
+            .find(|backup| backup.name.starts_with("pre-migration-v1-"))
+            .unwrap();
+        let restored = storage.restore_backup(&backup.name).unwrap();
+
+        // assert
+        assert_eq!(connections.len(), 1);
+        assert_eq!(rewritten["version"], CURRENT_VERSION);
+        assert!(rewritten["groups"].is_array());
+        assert_eq!(restored.connections.len(), 1);
+    }
+
+    #[test]
+    fn test_unparsed_entries_survive_saves() {
+        // arrange
+        let (storage, dir) = temp_storage();
+        let future_entry = serde_json::json!({"id": "x", "type": "teleport", "name": "later"});
+        let contents = serde_json::json!({
+            "version": CURRENT_VERSION,
+            "connections": [future_entry.clone()],
+        });
+        fs::write(dir.join("connections.json"), contents.to_string()).unwrap();
+
+        // act
+        storage.save_connection(connection("added")).unwrap();
+        let rewritten: Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("connections.json")).unwrap())
+                .unwrap();
+
+        // assert
+        assert_eq!(storage.load_connections().unwrap().len(), 1);
+        let entries = rewritten["connections"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains(&future_entry));
+    }
+
+    #[test]
+    fn test_refuses_newer_version() {
+        // arrange
+        let (storage, dir) = temp_storage();
+        let future = format!(
+            r#"{{"version": {}, "connections": []}}"#,
+            CURRENT_VERSION + 1
+        );
+        fs::write(dir.join("connections.json"), future).unwrap();
+
+        // act
+        let result = storage.load_connections();
+
+        // assert
+        assert!(matches!(result, Err(StorageError::UnsupportedVersion(_))));
     }
 }
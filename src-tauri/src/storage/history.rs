@@ -0,0 +1,236 @@
+use crate::models::{HealthResult, HealthStatus, HistoryEntry};
+use crate::services::downsample;
+use crate::storage::{config_dir, StorageError};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const RAW_RETENTION_HOURS: i64 = 24;
+const MAX_RETENTION_DAYS: i64 = 31;
+
+// Entries read so far from each history file, by connection, so a query only
+// parses the lines appended since the last one. Rewriting a file drops its index.
+static INDEX: Mutex<BTreeMap<PathBuf, HistoryIndex>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct HistoryIndex {
+    read_to: u64,
+    entries: HashMap<Uuid, Vec<HistoryEntry>>,
+}
+
+pub struct HistoryStorage {
+    history_path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl HistoryStorage {
+    pub fn new() -> Result<Self, StorageError> {
+        Ok(Self::with_path(config_dir()?.join("health-history.jsonl")))
+    }
+
+    pub fn with_path(history_path: PathBuf) -> Self {
+        Self {
+            history_path,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn append(&self, result: &HealthResult) -> Result<(), StorageError> {
+        if result.status == HealthStatus::Unknown {
+            return Ok(());
+        }
+
+        let mut line = serde_json::to_string(&HistoryEntry::from(result))?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.history_path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(&self, connection_id: Uuid) -> Result<Vec<HistoryEntry>, StorageError> {
+        let mut indexes = INDEX.lock().unwrap();
+        let index = indexes.entry(self.history_path.clone()).or_default();
+        self.catch_up(index)?;
+        Ok(index
+            .entries
+            .get(&connection_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    pub fn compact(&self, now: DateTime<Utc>) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap();
+
+        let raw_cutoff = now - Duration::hours(RAW_RETENTION_HOURS);
+        let oldest = now - Duration::days(MAX_RETENTION_DAYS);
+
+        let (old, recent): (Vec<_>, Vec<_>) = self
+            .read_all()?
+            .into_iter()
+            .filter(|e| e.checked_at >= oldest)
+            .partition(|e| e.checked_at < raw_cutoff);
+
+        self.write_all(downsample(old).iter().chain(recent.iter()))
+    }
+
+    pub fn remove(&self, connection_id: Uuid) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap();
+
+        let entries = self.read_all()?;
+        if entries.iter().all(|e| e.connection_id != connection_id) {
+            return Ok(());
+        }
+        self.write_all(entries.iter().filter(|e| e.connection_id != connection_id))
+    }
+
+    fn write_all<'a>(
+        &self,
+        entries: impl Iterator<Item = &'a HistoryEntry>,
+    ) -> Result<(), StorageError> {
+        // Held across the rename so no query reads the new file at the old offset.
+        let mut indexes = INDEX.lock().unwrap();
+        indexes.remove(&self.history_path);
+
+        let mut contents = String::new();
+        for entry in entries {
+            contents.push_str(&serde_json::to_string(entry)?);
+            contents.push('\n');
+        }
+
+        let temp_path = self.history_path.with_extension("jsonl.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, &self.history_path)?;
+        Ok(())
+    }
+
+    // Adds the lines appended since the index was last brought up to date. A
+    // line still being written (no newline yet) is left for the next query.
+    fn catch_up(&self, index: &mut HistoryIndex) -> Result<(), StorageError> {
+        let mut file = match fs::File::open(&self.history_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                *index = HistoryIndex::default();
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if file.metadata()?.len() < index.read_to {
+            *index = HistoryIndex::default();
+        }
+        file.seek(SeekFrom::Start(index.read_to))?;
+
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 || line.last() != Some(&b'\n') {
+                return Ok(());
+            }
+            index.read_to += read as u64;
+
+            if let Ok(entry) = serde_json::from_slice::<HistoryEntry>(&line) {
+                let entries = index.entries.entry(entry.connection_id).or_default();
+                let at = entries.partition_point(|e| e.checked_at <= entry.checked_at);
+                entries.insert(at, entry);
+            }
+        }
+    }
+
+    fn read_all(&self) -> Result<Vec<HistoryEntry>, StorageError> {
+        if !self.history_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = fs::File::open(&self.history_path)?;
+        let entries = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::TempDir;
+
+    fn temp_history() -> (HistoryStorage, TempDir) {
+        let dir = TempDir::new("history");
+        (
+            HistoryStorage::with_path(dir.join("health-history.jsonl")),
+            dir,
+        )
+    }
+
+    #[test]
+    fn test_compact_drops_expired_and_keeps_recent() {
+        // arrange
+        let (history, _dir) = temp_history();
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let mut expired = HealthResult::online(id, 5);
+        expired.checked_at = (now - Duration::days(40)).to_rfc3339();
+        history.append(&expired).unwrap();
+        history.append(&HealthResult::online(id, 7)).unwrap();
+        history.append(&HealthResult::unknown(id)).unwrap();
+
+        // act
+        history.compact(now).unwrap();
+        let entries = history.load(id).unwrap();
+
+        // assert
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].latency_ms, Some(7));
+    }
+
+    #[test]
+    fn test_remove_purges_only_that_connection() {
+        // arrange
+        let (history, _dir) = temp_history();
+        let (deleted, kept) = (Uuid::new_v4(), Uuid::new_v4());
+        history.append(&HealthResult::online(deleted, 5)).unwrap();
+        history.append(&HealthResult::online(kept, 7)).unwrap();
+
+        // act
+        history.remove(deleted).unwrap();
+
+        // assert
+        assert!(history.load(deleted).unwrap().is_empty());
+        assert_eq!(history.load(kept).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_load_picks_up_appends_and_rewrites() {
+        // arrange
+        let (history, _dir) = temp_history();
+        let id = Uuid::new_v4();
+        history.append(&HealthResult::online(id, 5)).unwrap();
+        let first = history.load(id).unwrap();
+        history.append(&HealthResult::online(id, 7)).unwrap();
+
+        // act
+        let appended = history.load(id).unwrap();
+        history.compact(Utc::now() + Duration::days(40)).unwrap();
+        let rewritten = history.load(id).unwrap();
+
+        // assert
+        assert_eq!(first.len(), 1);
+        assert_eq!(
+            appended.iter().map(|e| e.latency_ms).collect::<Vec<_>>(),
+            vec![Some(5), Some(7)]
+        );
+        assert!(rewritten.is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+use serde_json::{json, Value};
+
+type Migration = fn(Value) -> Value;
+
+// MIGRATIONS[n] upgrades a version n + 1 document to version n + 2. Append new
+// steps to the end; never change one that has shipped.
+const MIGRATIONS: [Migration; 2] = [wrap_connection_array, add_groups];
+
+pub const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+pub fn document_version(value: &Value) -> u32 {
+    match value {
+        Value::Array(_) => 1,
+        _ => value
+            .get("version")
+            .and_then(Value::as_u64)
+            .map_or(2, |version| version as u32),
+    }
+}
+
+pub fn migrate(mut value: Value, from: u32) -> Value {
+    for migration in MIGRATIONS.iter().skip(from.saturating_sub(1) as usize) {
+        value = migration(value);
+    }
+    value
+}
+
+// Version 1 was a bare array of connections.
+fn wrap_connection_array(connections: Value) -> Value {
+    json!({
+        "version": 2,
+        "connections": connections,
+        "settings": {},
+    })
+}
+
+// Version 3 added connection groups.
+fn add_groups(mut document: Value) -> Value {
+    document["version"] = json!(3);
+    if document.get("groups").is_none() {
+        document["groups"] = json!([]);
+    }
+    document
+}
@@ -1,3 +1,12 @@
 pub mod config;
+pub mod history;
+mod migrations;
+pub mod repository;
+pub mod secrets;
+#[cfg(test)]
+mod test_support;
 
 pub use config::*;
+pub use history::*;
+pub use repository::*;
+pub use secrets::*;
@@ -0,0 +1,343 @@
+use crate::models::{AppSettings, ConfigBackup, Connection, Group};
+use crate::storage::{ConfigDocument, ConfigStorage, StorageError};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+pub const CONNECTIONS_CHANGED_EVENT: &str = "connections-changed";
+pub const GROUPS_CHANGED_EVENT: &str = "groups-changed";
+
+// Loads connections.json once and serves reads from memory. Writes go through
+// `ConfigStorage` one at a time and then notify every subscriber.
+//
+// A file that cannot be loaded leaves the repository empty and read-only, so
+// the app still starts and nothing overwrites the file until a backup is
+// restored.
+pub struct ConnectionRepository {
+    storage: ConfigStorage,
+    load_error: RwLock<Option<String>>,
+    connections: RwLock<Vec<Connection>>,
+    groups: RwLock<Vec<Group>>,
+    settings: RwLock<AppSettings>,
+    write_lock: Mutex<()>,
+    changes: watch::Sender<()>,
+}
+
+impl ConnectionRepository {
+    pub fn load() -> Result<Self, StorageError> {
+        Self::from_storage(ConfigStorage::new()?)
+    }
+
+    pub fn with_path(config_path: PathBuf) -> Result<Self, StorageError> {
+        Self::from_storage(ConfigStorage::with_path(config_path))
+    }
+
+    fn from_storage(storage: ConfigStorage) -> Result<Self, StorageError> {
+        let (document, load_error) = match storage.load_document() {
+            Ok(document) => (document, None),
+            Err(e) => (ConfigDocument::default(), Some(e.to_string())),
+        };
+        Ok(Self {
+            storage,
+            load_error: RwLock::new(load_error),
+            connections: RwLock::new(document.connections),
+            groups: RwLock::new(document.groups),
+            settings: RwLock::new(document.settings),
+            write_lock: Mutex::new(()),
+            changes: watch::channel(()).0,
+        })
+    }
+
+    pub fn all(&self) -> Vec<Connection> {
+        self.connections.read().unwrap().clone()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Connection> {
+        self.connections
+            .read()
+            .unwrap()
+            .iter()
+            .find(|c| c.id == id)
+            .cloned()
+    }
+
+    pub fn groups(&self) -> Vec<Group> {
+        self.groups.read().unwrap().clone()
+    }
+
+    pub fn settings(&self) -> AppSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    // Why connections.json could not be loaded at startup, until a backup
+    // has been restored.
+    pub fn load_error(&self) -> Option<String> {
+        self.load_error.read().unwrap().clone()
+    }
+
+    pub fn save_settings(&self, settings: AppSettings) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap();
+        self.ensure_writable()?;
+        self.storage.save_settings(&settings)?;
+        *self.settings.write().unwrap() = settings;
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.changes.subscribe()
+    }
+
+    pub fn save(&self, connection: Connection) -> Result<(), StorageError> {
+        self.modify(|ConfigDocument { connections, .. }| {
+            if let Some(idx) = connections.iter().position(|c| c.id == connection.id) {
+                connections[idx] = connection;
+            } else {
+                connections.push(connection);
+            }
+        })
+    }
+
+    pub fn delete(&self, id: Uuid) -> Result<bool, StorageError> {
+        self.modify(|ConfigDocument { connections, .. }| {
+            let original_len = connections.len();
+            connections.retain(|c| c.id != id);
+            connections.len() != original_len
+        })
+    }
+
+    pub fn reorder(&self, ids: &[Uuid]) -> Result<(), StorageError> {
+        self.modify(|ConfigDocument { connections, .. }| {
+            for (index, id) in ids.iter().enumerate() {
+                if let Some(conn) = connections.iter_mut().find(|c| c.id == *id) {
+                    conn.order = index as i32;
+                }
+            }
+        })
+    }
+
+    pub fn save_group(&self, group: Group) -> Result<(), StorageError> {
+        self.modify(|ConfigDocument { groups, .. }| {
+            if let Some(idx) = groups.iter().position(|g| g.id == group.id) {
+                groups[idx] = group;
+            } else {
+                groups.push(group);
+            }
+        })
+    }
+
+    // Connections in a deleted group are kept and become ungrouped.
+    pub fn delete_group(&self, id: Uuid) -> Result<bool, StorageError> {
+        self.modify(|document| {
+            let original_len = document.groups.len();
+            document.groups.retain(|g| g.id != id);
+            if document.groups.len() == original_len {
+                return false;
+            }
+            for connection in &mut document.connections {
+                if connection.group_id == Some(id) {
+                    connection.group_id = None;
+                }
+            }
+            true
+        })
+    }
+
+    pub fn reorder_groups(&self, ids: &[Uuid]) -> Result<(), StorageError> {
+        self.modify(|ConfigDocument { groups, .. }| {
+            for (index, id) in ids.iter().enumerate() {
+                if let Some(group) = groups.iter_mut().find(|g| g.id == *id) {
+                    group.order = index as i32;
+                }
+            }
+        })
+    }
+
+    // Puts a connection into `group_id` (or the ungrouped list) at `index`,
+    // or at the end, and renumbers that list.
+    pub fn move_connection(
+        &self,
+        id: Uuid,
+        group_id: Option<Uuid>,
+        index: Option<usize>,
+    ) -> Result<bool, StorageError> {
+        self.modify(|ConfigDocument { connections, .. }| {
+            if !connections.iter().any(|c| c.id == id) {
+                return false;
+            }
+            let mut siblings: Vec<_> = connections
+                .iter()
+                .filter(|c| c.group_id == group_id && c.id != id)
+                .map(|c| (c.order, c.id))
+                .collect();
+            siblings.sort();
+            let mut ids: Vec<_> = siblings.into_iter().map(|(_, id)| id).collect();
+            ids.insert(index.unwrap_or(ids.len()).min(ids.len()), id);
+
+            for connection in connections.iter_mut() {
+                if let Some(position) = ids.iter().position(|i| *i == connection.id) {
+                    connection.group_id = group_id;
+                    connection.order = position as i32;
+                }
+            }
+            true
+        })
+    }
+
+    pub fn list_backups(&self) -> Result<Vec<ConfigBackup>, StorageError> {
+        self.storage.list_backups()
+    }
+
+    pub fn restore_backup(&self, name: &str) -> Result<Vec<Connection>, StorageError> {
+        let _guard = self.write_lock.lock().unwrap();
+        let document = self.storage.restore_backup(name)?;
+        *self.load_error.write().unwrap() = None;
+        *self.settings.write().unwrap() = document.settings;
+        self.replace(document.connections.clone(), document.groups);
+        Ok(document.connections)
+    }
+
+    // The file is re-read under its lock, so edits made by another instance
+    // since we loaded are kept rather than overwritten by our cached copy.
+    // Anything `modify` looks up should come from the document it is given
+    // for the same reason.
+    fn modify<T>(&self, modify: impl FnOnce(&mut ConfigDocument) -> T) -> Result<T, StorageError> {
+        let _guard = self.write_lock.lock().unwrap();
+        self.ensure_writable()?;
+        let mut updated = ConfigDocument::default();
+        let result = self.storage.update_document(|document| {
+            let result = modify(document);
+            updated = document.clone();
+            result
+        })?;
+        self.replace(updated.connections, updated.groups);
+        Ok(result)
+    }
+
+    fn ensure_writable(&self) -> Result<(), StorageError> {
+        match self.load_error() {
+            Some(reason) => Err(StorageError::LoadFailed(reason)),
+            None => Ok(()),
+        }
+    }
+
+    fn replace(&self, connections: Vec<Connection>, groups: Vec<Group>) {
+        *self.connections.write().unwrap() = connections;
+        *self.groups.write().unwrap() = groups;
+        self.changes.send_replace(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::{connection, TempDir};
+    use std::fs;
+
+    #[test]
+    fn test_repository_persists_and_notifies() {
+        // arrange
+        let dir = TempDir::new("repo");
+        let path = dir.join("connections.json");
+        let repository = ConnectionRepository::with_path(path.clone()).unwrap();
+        let changes = repository.subscribe();
+        let nas = connection("nas");
+
+        // act
+        repository.save(nas.clone()).unwrap();
+        let deleted_missing = repository.delete(Uuid::new_v4()).unwrap();
+        let reloaded = ConnectionRepository::with_path(path).unwrap();
+
+        // assert
+        assert!(changes.has_changed().unwrap());
+        assert!(!deleted_missing);
+        assert_eq!(repository.get(nas.id).map(|c| c.name), Some("nas".into()));
+        assert_eq!(reloaded.all().len(), 1);
+    }
+
+    #[test]
+    fn test_unreadable_file_starts_read_only_until_restored() {
+        // arrange
+        let dir = TempDir::new("repo");
+        let path = dir.join("connections.json");
+        let writer = ConnectionRepository::with_path(path.clone()).unwrap();
+        writer.save(connection("nas")).unwrap();
+        writer.save(connection("router")).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        // act
+        let repository = ConnectionRepository::with_path(path.clone()).unwrap();
+        let save = repository.save(connection("printer"));
+        let backup = repository.list_backups().unwrap()[0].name.clone();
+        repository.restore_backup(&backup).unwrap();
+
+        // assert
+        assert!(matches!(save, Err(StorageError::LoadFailed(_))));
+        // The unreadable file is kept among the backups.
+        assert_eq!(repository.list_backups().unwrap().len(), 2);
+        assert_eq!(repository.load_error(), None);
+        assert_eq!(repository.all().len(), 1);
+        assert!(repository.save(connection("printer")).is_ok());
+    }
+
+    #[test]
+    fn test_lookups_see_edits_from_another_instance() {
+        // arrange
+        let dir = TempDir::new("repo");
+        let path = dir.join("connections.json");
+        let repository = ConnectionRepository::with_path(path.clone()).unwrap();
+        let other = ConnectionRepository::with_path(path).unwrap();
+        let (nas, router) = (connection("nas"), connection("router"));
+        let lab = Group::new("Home lab".into(), 0);
+        other.save(nas.clone()).unwrap();
+        other.save(router.clone()).unwrap();
+        other.save_group(lab.clone()).unwrap();
+
+        // act
+        let moved = repository
+            .move_connection(router.id, None, Some(0))
+            .unwrap();
+        let deleted = repository.delete(nas.id).unwrap();
+        let deleted_group = repository.delete_group(lab.id).unwrap();
+
+        // assert
+        assert!(moved && deleted && deleted_group);
+        let names: Vec<_> = repository.all().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["router"]);
+        assert!(repository.groups().is_empty());
+    }
+
+    #[test]
+    fn test_move_connection_between_groups() {
+        // arrange
+        let dir = TempDir::new("repo");
+        let repository = ConnectionRepository::with_path(dir.join("connections.json")).unwrap();
+        let lab = Group::new("Home lab".into(), 0);
+        let (nas, router, printer) = (
+            connection("nas"),
+            connection("router"),
+            connection("printer"),
+        );
+        repository.save_group(lab.clone()).unwrap();
+        for conn in [&nas, &router, &printer] {
+            repository.save(conn.clone()).unwrap();
+            repository
+                .move_connection(conn.id, Some(lab.id), None)
+                .unwrap();
+        }
+
+        // act
+        repository
+            .move_connection(printer.id, Some(lab.id), Some(0))
+            .unwrap();
+        repository.delete_group(lab.id).unwrap();
+
+        // assert
+        let mut connections = repository.all();
+        connections.sort_by_key(|c| c.order);
+        let names: Vec<_> = connections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["printer", "nas", "router"]);
+        assert!(connections.iter().all(|c| c.group_id.is_none()));
+        assert!(repository.groups().is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::models::AlertChannel;
+use crate::storage::{config_dir, StorageError};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use uuid::Uuid;
+
+const NONCE_LEN: usize = 12;
+const KEYRING_SERVICE: &str = "turbo-waffle";
+const KEYRING_USER: &str = "secrets-key";
+
+// Every instance shares the one secrets file, so writes are serialized here
+// as well as by the file lock, which only guards against a second instance.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+static KEYRING_KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+// Secrets are kept as an encrypted JSON map of connection id to secret, so a
+// copied or shared connections.json never carries credentials with it. The key
+// is kept in the OS keyring, away from the config directory and its backups.
+// Without a keyring it falls back to secrets.key beside the secrets, which
+// only keeps them from casual view.
+pub struct SecretStorage {
+    secrets_path: PathBuf,
+    key_path: PathBuf,
+    use_keyring: bool,
+}
+
+impl SecretStorage {
+    pub fn new() -> Result<Self, StorageError> {
+        Ok(Self {
+            use_keyring: true,
+            ..Self::with_dir(&config_dir()?)
+        })
+    }
+
+    // Keeps the key in secrets.key only, leaving the keyring alone.
+    pub fn with_dir(dir: &Path) -> Self {
+        Self {
+            secrets_path: dir.join("secrets.bin"),
+            key_path: dir.join("secrets.key"),
+            use_keyring: false,
+        }
+    }
+
+    pub fn get(&self, connection_id: Uuid) -> Result<Option<String>, StorageError> {
+        Ok(self.load()?.remove(&connection_id))
+    }
+
+    pub fn set(&self, connection_id: Uuid, secret: String) -> Result<(), StorageError> {
+        self.update(|secrets| {
+            secrets.insert(connection_id, secret);
+            true
+        })
+    }
+
+    pub fn remove(&self, connection_id: Uuid) -> Result<(), StorageError> {
+        self.update(|secrets| secrets.remove(&connection_id).is_some())
+    }
+
+    // Webhook headers often carry credentials (an `Authorization` value, say),
+    // so they are kept here under the channel id and left out of
+    // connections.json. Channels that still have headers inline keep them.
+    pub fn load_alert_headers(&self, channels: &mut [AlertChannel]) -> Result<(), StorageError> {
+        let secrets = self.load()?;
+        for channel in channels.iter_mut().filter(|c| c.headers.is_empty()) {
+            if let Some(headers) = secrets.get(&channel.id) {
+                channel.headers = serde_json::from_str(headers)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Moves each channel's headers in here, leaving the channels ready to be
+    // written to connections.json.
+    pub fn store_alert_headers(&self, channels: &mut [AlertChannel]) -> Result<(), StorageError> {
+        let mut stored = Vec::with_capacity(channels.len());
+        for channel in channels {
+            let headers = std::mem::take(&mut channel.headers);
+            let headers = (!headers.is_empty())
+                .then(|| serde_json::to_string(&headers))
+                .transpose()?;
+            stored.push((channel.id, headers));
+        }
+
+        self.update(|secrets| {
+            let mut changed = false;
+            for (id, headers) in stored {
+                changed |= match headers {
+                    Some(headers) => secrets.insert(id, headers.clone()) != Some(headers),
+                    None => secrets.remove(&id).is_some(),
+                };
+            }
+            changed
+        })
+    }
+
+    // Holds the secrets lock across the whole load-modify-save. `modify`
+    // returns whether it changed anything. A file that can no longer be read
+    // is moved aside rather than blocking every save, and can be put back once
+    // its key is restored; nothing is removed from it, as a delete is not
+    // worth failing over.
+    fn update(
+        &self,
+        modify: impl FnOnce(&mut HashMap<Uuid, String>) -> bool,
+    ) -> Result<(), StorageError> {
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let _lock = self.lock()?;
+
+        let (mut secrets, unreadable) = match self.load() {
+            Err(StorageError::Decrypt | StorageError::SecretsKeyMissing) => (HashMap::new(), true),
+            loaded => (loaded?, false),
+        };
+        if !modify(&mut secrets) {
+            return Ok(());
+        }
+        if unreadable {
+            self.set_aside()?;
+        }
+        self.save(&secrets)
+    }
+
+    fn lock(&self) -> Result<File, StorageError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.secrets_path.with_extension("bin.lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    fn load(&self) -> Result<HashMap<Uuid, String>, StorageError> {
+        if !self.secrets_path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let contents = fs::read(&self.secrets_path)?;
+        if contents.len() < NONCE_LEN {
+            return Err(StorageError::Decrypt);
+        }
+
+        let (nonce, ciphertext) = contents.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher(false)?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| StorageError::Decrypt)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn save(&self, secrets: &HashMap<Uuid, String>) -> Result<(), StorageError> {
+        let plaintext = serde_json::to_vec(secrets)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher(true)?
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| StorageError::Decrypt)?;
+
+        let mut contents = nonce.to_vec();
+        contents.extend_from_slice(&ciphertext);
+
+        let temp_path = self.secrets_path.with_extension("bin.tmp");
+        write_private(&temp_path, &contents)?;
+        fs::rename(&temp_path, &self.secrets_path)?;
+        Ok(())
+    }
+
+    fn set_aside(&self) -> Result<(), StorageError> {
+        let name = format!(
+            "secrets.unreadable-{}.bin",
+            Utc::now().format("%Y%m%d-%H%M%S")
+        );
+        fs::rename(&self.secrets_path, self.secrets_path.with_file_name(name))?;
+        Ok(())
+    }
+
+    // A new key is only made when writing; reading without one means the
+    // existing secrets.bin can never be decrypted.
+    fn cipher(&self, create: bool) -> Result<ChaCha20Poly1305, StorageError> {
+        let key = match self.keyring_key(create)? {
+            Some(key) => key,
+            None => self.file_key(create)?,
+        };
+        if key.len() != 32 {
+            return Err(StorageError::Decrypt);
+        }
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    // None when there is no keyring to use, as on a Linux desktop without a
+    // Secret Service running. A secrets.key left from before is moved into
+    // the keyring the first time one is there.
+    fn keyring_key(&self, create: bool) -> Result<Option<Vec<u8>>, StorageError> {
+        if !self.use_keyring {
+            return Ok(None);
+        }
+        if let Some(key) = KEYRING_KEY.get() {
+            return Ok(Some(key.clone()));
+        }
+        let Ok(entry) = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER) else {
+            return Ok(None);
+        };
+
+        let key = match entry.get_secret() {
+            Ok(key) => key,
+            Err(keyring::Error::NoEntry) => {
+                let key = if self.key_path.exists() {
+                    fs::read(&self.key_path)?
+                } else if create {
+                    ChaCha20Poly1305::generate_key(&mut OsRng).to_vec()
+                } else {
+                    return Err(StorageError::SecretsKeyMissing);
+                };
+                if entry.set_secret(&key).is_err() {
+                    return Ok(None);
+                }
+                if self.key_path.exists() {
+                    fs::remove_file(&self.key_path)?;
+                }
+                key
+            }
+            Err(_) => return Ok(None),
+        };
+        Ok(Some(KEYRING_KEY.get_or_init(|| key).clone()))
+    }
+
+    fn file_key(&self, create: bool) -> Result<Vec<u8>, StorageError> {
+        if self.key_path.exists() {
+            return Ok(fs::read(&self.key_path)?);
+        }
+        if !create {
+            return Err(StorageError::SecretsKeyMissing);
+        }
+
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        write_private(&self.key_path, &key)?;
+        Ok(key.to_vec())
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), StorageError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::TempDir;
+
+    #[test]
+    fn test_secrets_round_trip_encrypted() {
+        // arrange
+        let dir = TempDir::new("secrets");
+        let secrets = SecretStorage::with_dir(&dir);
+        let id = Uuid::new_v4();
+
+        // act
+        secrets.set(id, "hunter2".into()).unwrap();
+        let stored = secrets.get(id).unwrap();
+        let on_disk = fs::read(dir.join("secrets.bin")).unwrap();
+        secrets.remove(id).unwrap();
+
+        // assert
+        assert_eq!(stored.as_deref(), Some("hunter2"));
+        assert!(!on_disk.windows(7).any(|w| w == b"hunter2"));
+        assert_eq!(secrets.get(id).unwrap(), None);
+    }
+
+    #[test]
+    fn test_alert_headers_round_trip() {
+        // arrange
+        let dir = TempDir::new("secrets");
+        let secrets = SecretStorage::with_dir(&dir);
+        let channel = AlertChannel {
+            id: Uuid::new_v4(),
+            name: "Chat".into(),
+            enabled: true,
+            url: "https://chat.example/hook".into(),
+            method: "POST".into(),
+            headers: [("Authorization".to_string(), "Bearer hunter2".to_string())].into(),
+            body_template: String::new(),
+        };
+        let mut channels = vec![channel.clone()];
+
+        // act
+        secrets.store_alert_headers(&mut channels).unwrap();
+        let stripped = channels[0].headers.is_empty();
+        secrets.load_alert_headers(&mut channels).unwrap();
+
+        // assert
+        assert!(stripped);
+        assert_eq!(channels, vec![channel]);
+    }
+
+    #[test]
+    fn test_concurrent_sets_keep_every_secret() {
+        // arrange
+        let dir = TempDir::new("secrets");
+        let ids: Vec<Uuid> = (0..8).map(|_| Uuid::new_v4()).collect();
+
+        // act
+        std::thread::scope(|scope| {
+            for &id in &ids {
+                let dir = &dir;
+                scope.spawn(move || {
+                    SecretStorage::with_dir(dir)
+                        .set(id, id.to_string())
+                        .unwrap()
+                });
+            }
+        });
+
+        // assert
+        let secrets = SecretStorage::with_dir(&dir);
+        for id in ids {
+            assert_eq!(secrets.get(id).unwrap(), Some(id.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_lost_key_does_not_block_saves() {
+        // arrange
+        let dir = TempDir::new("secrets");
+        let secrets = SecretStorage::with_dir(&dir);
+        let (old, new) = (Uuid::new_v4(), Uuid::new_v4());
+        secrets.set(old, "hunter2".into()).unwrap();
+        fs::remove_file(dir.join("secrets.key")).unwrap();
+
+        // act
+        let read = secrets.get(old);
+        let removed = secrets.remove(old);
+        secrets.set(new, "correct horse".into()).unwrap();
+
+        // assert
+        assert!(matches!(read, Err(StorageError::SecretsKeyMissing)));
+        assert!(removed.is_ok());
+        assert_eq!(secrets.get(new).unwrap().as_deref(), Some("correct horse"));
+        assert_eq!(secrets.get(old).unwrap(), None);
+        let set_aside = fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("secrets.unreadable-")
+            })
+            .count();
+        assert_eq!(set_aside, 1);
+    }
+}
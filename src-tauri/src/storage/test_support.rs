@@ -0,0 +1,52 @@
+use crate::models::{Connection, ConnectionConfig};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+// A fresh directory under the system temp dir. It is removed on drop, so a
+// test that panics cleans up after itself too.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("turbo-waffle-{}-{}", prefix, Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn connection(name: &str) -> Connection {
+    Connection::new(
+        name.into(),
+        ConnectionConfig::Ssh {
+            host: "10.0.0.1".into(),
+            port: 22,
+            username: "root".into(),
+            wol_enabled: false,
+            mac_address: None,
+            broadcast_addr: None,
+        },
+        0,
+    )
+}
@@ -0,0 +1,648 @@
+use crate::commands::{open_connection, send_wol, CommandError};
+use crate::models::{Connection, Group, HealthResult, HealthStatus};
+use crate::services::HealthScheduler;
+use crate::storage::{ConnectionRepository, CONNECTIONS_CHANGED_EVENT, GROUPS_CHANGED_EVENT};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuBuilder, MenuEvent, MenuItemBuilder, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_notification::NotificationExt;
+use uuid::Uuid;
+
+pub const TRAY_ID: &str = "main_tray";
+pub const EDIT_CONNECTION_EVENT: &str = "edit-connection";
+
+// Health results arrive one connection at a time; waiting a moment lets a
+// burst of them land in a single update.
+const REFRESH_DEBOUNCE_MS: u64 = 500;
+// How often latency-only changes are written into the menu.
+const LABEL_REFRESH_SECS: u64 = 10;
+
+const DEGRADED_BADGE: [u8; 4] = [0xf5, 0x9e, 0x0b, 0xff];
+const OFFLINE_BADGE: [u8; 4] = [0xef, 0x44, 0x44, 0xff];
+
+// Menu item ids are `<action>:<connection id>` for per-connection items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayAction {
+    Open(Uuid),
+    Wake(Uuid),
+    Check(Uuid),
+    CopyAddress(Uuid),
+    Edit(Uuid),
+    ShowMain,
+    Exit,
+}
+
+impl TrayAction {
+    pub fn parse(id: &str) -> Option<Self> {
+        match id {
+            "show_main" => return Some(Self::ShowMain),
+            "exit" => return Some(Self::Exit),
+            _ => {}
+        }
+
+        let (action, id) = id.split_once(':')?;
+        let id = Uuid::parse_str(id).ok()?;
+        match action {
+            "connect" => Some(Self::Open(id)),
+            "wake" => Some(Self::Wake(id)),
+            "check" => Some(Self::Check(id)),
+            "copy" => Some(Self::CopyAddress(id)),
+            "edit" => Some(Self::Edit(id)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AggregateHealth {
+    #[default]
+    Healthy,
+    Degraded,
+    Down,
+}
+
+pub fn aggregate_health<'a>(
+    results: impl IntoIterator<Item = &'a HealthResult>,
+) -> AggregateHealth {
+    results
+        .into_iter()
+        .fold(AggregateHealth::Healthy, |aggregate, result| {
+            match (aggregate, &result.status) {
+                (_, HealthStatus::Offline) | (AggregateHealth::Down, _) => AggregateHealth::Down,
+                (_, HealthStatus::Degraded) => AggregateHealth::Degraded,
+                _ => aggregate,
+            }
+        })
+}
+
+pub fn tray_label(connection: &Connection, result: Option<&HealthResult>) -> String {
+    let Some(result) = result else {
+        return format!("⚪ {}", connection.name);
+    };
+
+    let marker = match result.status {
+        HealthStatus::Online => "🟢",
+        HealthStatus::Degraded => "🟡",
+        HealthStatus::Offline => "🔴",
+        HealthStatus::Unknown => "⚪",
+    };
+    match (&result.status, result.latency_ms) {
+        (HealthStatus::Offline, _) => format!("{} {} (offline)", marker, connection.name),
+        (_, Some(latency)) => format!("{} {} ({} ms)", marker, connection.name, latency),
+        _ => format!("{} {}", marker, connection.name),
+    }
+}
+
+// Everything the menu shows, reduced to what decides whether it has to be
+// rebuilt or only relabelled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraySnapshot {
+    // Item ids in menu order, with their parent and the actions they offer.
+    layout: Vec<String>,
+    labels: HashMap<String, String>,
+    statuses: HashMap<Uuid, HealthStatus>,
+    health: AggregateHealth,
+}
+
+impl TraySnapshot {
+    pub fn new(
+        connections: &[Connection],
+        groups: &[Group],
+        latest: &HashMap<Uuid, HealthResult>,
+    ) -> Self {
+        let mut snapshot = Self {
+            statuses: latest
+                .iter()
+                .map(|(id, result)| (*id, result.status.clone()))
+                .collect(),
+            health: aggregate_health(latest.values()),
+            ..Self::default()
+        };
+
+        for entry in tray_entries(connections, groups) {
+            match entry {
+                TrayEntry::Group(group, members) => {
+                    let id = group_item_id(group.id);
+                    let health = aggregate_health(members.iter().filter_map(|c| latest.get(&c.id)));
+                    snapshot.layout.push(id.clone());
+                    snapshot
+                        .labels
+                        .insert(id, format!("{} {}", health_marker(health), group.name));
+                    for connection in members {
+                        snapshot.add_connection(connection, Some(group.id), latest);
+                    }
+                }
+                TrayEntry::Connection(connection) => {
+                    snapshot.add_connection(connection, None, latest);
+                }
+            }
+        }
+        snapshot
+    }
+
+    fn add_connection(
+        &mut self,
+        connection: &Connection,
+        group_id: Option<Uuid>,
+        latest: &HashMap<Uuid, HealthResult>,
+    ) {
+        let id = connection_item_id(connection.id);
+        self.layout.push(format!(
+            "{}/{} open={} wake={}",
+            group_id.map(group_item_id).unwrap_or_default(),
+            id,
+            connection.config.can_open(),
+            connection.config.wol_enabled(),
+        ));
+        self.labels
+            .insert(id, tray_label(connection, latest.get(&connection.id)));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrayUpdate {
+    Rebuild,
+    Relabel,
+    Nothing,
+}
+
+// Replacing the menu closes it if the user has it open, so it is only rebuilt
+// when items come or go. Status changes are relabelled straight away and
+// latency-only changes once `labels_due` says enough time has passed.
+pub fn plan_tray_update(shown: &TraySnapshot, next: &TraySnapshot, labels_due: bool) -> TrayUpdate {
+    if shown.layout != next.layout {
+        TrayUpdate::Rebuild
+    } else if shown.statuses != next.statuses || (labels_due && shown.labels != next.labels) {
+        TrayUpdate::Relabel
+    } else {
+        TrayUpdate::Nothing
+    }
+}
+
+enum TrayEntry<'a> {
+    Group(&'a Group, Vec<&'a Connection>),
+    Connection(&'a Connection),
+}
+
+fn tray_entries<'a>(connections: &'a [Connection], groups: &'a [Group]) -> Vec<TrayEntry<'a>> {
+    let mut connections: Vec<&Connection> = connections.iter().collect();
+    let mut groups: Vec<&Group> = groups.iter().collect();
+    connections.sort_by(|a, b| {
+        a.order
+            .cmp(&b.order)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    groups.sort_by(|a, b| {
+        a.order
+            .cmp(&b.order)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+
+    let mut entries: Vec<TrayEntry> = groups
+        .iter()
+        .map(|group| {
+            let members = connections
+                .iter()
+                .copied()
+                .filter(|c| c.group_id == Some(group.id))
+                .collect();
+            TrayEntry::Group(group, members)
+        })
+        .collect();
+    // Connections whose group no longer exists are shown ungrouped.
+    entries.extend(
+        connections
+            .iter()
+            .copied()
+            .filter(|c| {
+                c.group_id
+                    .is_none_or(|id| !groups.iter().any(|g| g.id == id))
+            })
+            .map(TrayEntry::Connection),
+    );
+    entries
+}
+
+fn group_item_id(id: Uuid) -> String {
+    format!("group:{}", id)
+}
+
+fn connection_item_id(id: Uuid) -> String {
+    format!("connection:{}", id)
+}
+
+// The menu the tray is showing, with handles to every submenu so their text
+// can be changed in place.
+pub struct TrayMenu<R: Runtime> {
+    pub menu: Menu<R>,
+    snapshot: TraySnapshot,
+    submenus: HashMap<String, Submenu<R>>,
+    relabelled_at: Instant,
+}
+
+impl<R: Runtime> TrayMenu<R> {
+    pub fn build<M: Manager<R>>(manager: &M) -> tauri::Result<Self> {
+        let repository = manager.state::<ConnectionRepository>();
+        let (connections, groups) = (repository.all(), repository.groups());
+        let snapshot = TraySnapshot::new(&connections, &groups, &latest_results(manager));
+        Self::from_snapshot(manager, &connections, &groups, snapshot)
+    }
+
+    fn from_snapshot<M: Manager<R>>(
+        manager: &M,
+        connections: &[Connection],
+        groups: &[Group],
+        snapshot: TraySnapshot,
+    ) -> tauri::Result<Self> {
+        let mut submenus = HashMap::new();
+        let mut builder = MenuBuilder::new(manager);
+
+        if connections.is_empty() && groups.is_empty() {
+            builder = builder.text("connections_empty", "No connections");
+        }
+
+        for entry in tray_entries(connections, groups) {
+            let submenu = match entry {
+                TrayEntry::Group(group, members) => {
+                    let id = group_item_id(group.id);
+                    let mut submenu = SubmenuBuilder::with_id(manager, &id, label(&snapshot, &id));
+                    if members.is_empty() {
+                        submenu = submenu.item(
+                            &MenuItemBuilder::new("No connections")
+                                .enabled(false)
+                                .build(manager)?,
+                        );
+                    }
+                    for connection in members {
+                        let item = connection_submenu(manager, connection, &snapshot)?;
+                        submenus.insert(item.id().0.clone(), item.clone());
+                        submenu = submenu.item(&item);
+                    }
+                    submenu.build()?
+                }
+                TrayEntry::Connection(connection) => {
+                    connection_submenu(manager, connection, &snapshot)?
+                }
+            };
+            submenus.insert(submenu.id().0.clone(), submenu.clone());
+            builder = builder.item(&submenu);
+        }
+
+        let menu = builder
+            .separator()
+            .text("show_main", "Open Turbo Waffle")
+            .separator()
+            .text("exit", "Exit")
+            .build()?;
+
+        Ok(Self {
+            menu,
+            snapshot,
+            submenus,
+            relabelled_at: Instant::now(),
+        })
+    }
+
+    fn relabel(&mut self, snapshot: TraySnapshot) {
+        for (id, text) in &snapshot.labels {
+            if self.snapshot.labels.get(id) != Some(text) {
+                if let Some(submenu) = self.submenus.get(id) {
+                    let _ = submenu.set_text(text);
+                }
+            }
+        }
+        self.snapshot = snapshot;
+        self.relabelled_at = Instant::now();
+    }
+}
+
+fn label(snapshot: &TraySnapshot, id: &str) -> String {
+    snapshot.labels.get(id).cloned().unwrap_or_default()
+}
+
+fn health_marker(health: AggregateHealth) -> &'static str {
+    match health {
+        AggregateHealth::Healthy => "📁",
+        AggregateHealth::Degraded => "🟡",
+        AggregateHealth::Down => "🔴",
+    }
+}
+
+fn connection_submenu<R: Runtime, M: Manager<R>>(
+    manager: &M,
+    connection: &Connection,
+    snapshot: &TraySnapshot,
+) -> tauri::Result<Submenu<R>> {
+    let id = connection.id;
+    let item_id = connection_item_id(id);
+    let mut builder = SubmenuBuilder::with_id(manager, &item_id, label(snapshot, &item_id));
+    if connection.config.can_open() {
+        builder = builder.text(format!("connect:{}", id), "Open");
+    }
+    if connection.config.wol_enabled() {
+        builder = builder.text(format!("wake:{}", id), "Wake (Wake-on-LAN)");
+    }
+
+    builder
+        .text(format!("check:{}", id), "Check now")
+        .text(format!("copy:{}", id), "Copy address")
+        .separator()
+        .text(format!("edit:{}", id), "Edit...")
+        .build()
+}
+
+pub fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let Some(action) = TrayAction::parse(&event.id().0) else {
+        return;
+    };
+
+    match action {
+        TrayAction::Exit => app.exit(0),
+        TrayAction::ShowMain => show_main_window(app),
+        TrayAction::Open(id) => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = open_connection(app.clone(), id.to_string()).await {
+                    notify_failure(&app, id, &e);
+                }
+            });
+        }
+        TrayAction::Wake(id) => {
+            if let Err(e) = send_wol(app.state(), id.to_string()) {
+                notify_failure(app, id, &e);
+            }
+        }
+        TrayAction::Check(id) => app.state::<HealthScheduler>().check_soon(id),
+        TrayAction::CopyAddress(id) => {
+            if let Some(connection) = app.state::<ConnectionRepository>().get(id) {
+                let _ = app.clipboard().write_text(connection.config.address());
+            }
+        }
+        TrayAction::Edit(id) => {
+            show_main_window(app);
+            let _ = app.emit(EDIT_CONNECTION_EVENT, id);
+        }
+    }
+}
+
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+// The tray has no window to show a toast in, so failures surface as a
+// desktop notification instead.
+fn notify_failure<R: Runtime>(app: &AppHandle<R>, id: Uuid, error: &CommandError) {
+    let name = app
+        .state::<ConnectionRepository>()
+        .get(id)
+        .map(|c| c.name)
+        .unwrap_or_else(|| "Connection".into());
+    let body = match error.details() {
+        Some(details) => format!("{}: {}", error, details),
+        None => error.to_string(),
+    };
+    let _ = app.notification().builder().title(name).body(body).show();
+}
+
+pub fn tray_icon(health: AggregateHealth) -> Image<'static> {
+    let base = tauri::include_image!("icons/tray-32.png");
+    match health {
+        AggregateHealth::Healthy => base,
+        AggregateHealth::Degraded => with_badge(&base, DEGRADED_BADGE),
+        AggregateHealth::Down => with_badge(&base, OFFLINE_BADGE),
+    }
+}
+
+// Paints a filled circle into the bottom-right corner of the icon.
+fn with_badge(base: &Image<'_>, color: [u8; 4]) -> Image<'static> {
+    let (width, height) = (base.width(), base.height());
+    let mut rgba = base.rgba().to_vec();
+    let radius = width.min(height) as f32 * 0.22;
+    let (cx, cy) = (width as f32 - radius - 1.0, height as f32 - radius - 1.0);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                let offset = ((y * width + x) * 4) as usize;
+                rgba[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    Image::new_owned(rgba, width, height)
+}
+
+fn latest_results<R: Runtime, M: Manager<R>>(manager: &M) -> HashMap<Uuid, HealthResult> {
+    manager
+        .try_state::<HealthScheduler>()
+        .map(|scheduler| {
+            scheduler
+                .latest()
+                .into_iter()
+                .map(|result| (result.connection_id, result))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn refresh_tray<R: Runtime>(app: &AppHandle<R>, tray_menu: &mut TrayMenu<R>, labels_due: bool) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let repository = app.state::<ConnectionRepository>();
+    let (connections, groups) = (repository.all(), repository.groups());
+    let snapshot = TraySnapshot::new(&connections, &groups, &latest_results(app));
+    let labels_due =
+        labels_due || tray_menu.relabelled_at.elapsed() >= Duration::from_secs(LABEL_REFRESH_SECS);
+
+    let health_changed = snapshot.health != tray_menu.snapshot.health;
+    let health = snapshot.health;
+    match plan_tray_update(&tray_menu.snapshot, &snapshot, labels_due) {
+        TrayUpdate::Rebuild => {
+            if let Ok(rebuilt) = TrayMenu::from_snapshot(app, &connections, &groups, snapshot) {
+                let _ = tray.set_menu(Some(rebuilt.menu.clone()));
+                *tray_menu = rebuilt;
+            }
+        }
+        TrayUpdate::Relabel => tray_menu.relabel(snapshot),
+        TrayUpdate::Nothing => {}
+    }
+
+    if health_changed {
+        let _ = tray.set_icon(Some(tray_icon(health)));
+        // A template icon is drawn in one colour on macOS, which would hide the badge.
+        let _ = tray.set_icon_as_template(health == AggregateHealth::Healthy);
+    }
+}
+
+// Keeps the tray and the UI in step with every saved change and every health
+// result.
+pub fn watch_tray<R: Runtime>(app: AppHandle<R>, mut tray_menu: TrayMenu<R>) {
+    let mut connections = app.state::<ConnectionRepository>().subscribe();
+    let mut results = app.state::<HealthScheduler>().subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::select! {
+                changed = connections.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let repository = app.state::<ConnectionRepository>();
+                    let _ = app.emit(CONNECTIONS_CHANGED_EVENT, repository.all());
+                    let _ = app.emit(GROUPS_CHANGED_EVENT, repository.groups());
+                    // A rename should show up straight away.
+                    refresh_tray(&app, &mut tray_menu, true);
+                }
+                changed = results.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(REFRESH_DEBOUNCE_MS)).await;
+                    results.mark_unchanged();
+                    refresh_tray(&app, &mut tray_menu, false);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ConnectionConfig;
+
+    #[test]
+    fn test_aggregate_health_prefers_worst_status() {
+        // arrange
+        let id = Uuid::new_v4();
+        let healthy = [HealthResult::online(id, 12), HealthResult::unknown(id)];
+        let degraded = [
+            HealthResult::online(id, 12),
+            HealthResult::degraded(id, 900, "slow".into()),
+        ];
+        let down = [
+            HealthResult::offline(id, None),
+            HealthResult::degraded(id, 900, "slow".into()),
+        ];
+
+        // act
+        let results = [
+            aggregate_health(&healthy),
+            aggregate_health(&degraded),
+            aggregate_health(&down),
+            aggregate_health(&[]),
+        ];
+
+        // assert
+        assert_eq!(
+            results,
+            [
+                AggregateHealth::Healthy,
+                AggregateHealth::Degraded,
+                AggregateHealth::Down,
+                AggregateHealth::Healthy,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_tray_action() {
+        // arrange
+        let id = Uuid::new_v4();
+
+        // act
+        let wake = TrayAction::parse(&format!("wake:{}", id));
+        let copy = TrayAction::parse(&format!("copy:{}", id));
+        let exit = TrayAction::parse("exit");
+        let invalid = TrayAction::parse("wake:not-a-uuid");
+
+        // assert
+        assert_eq!(wake, Some(TrayAction::Wake(id)));
+        assert_eq!(copy, Some(TrayAction::CopyAddress(id)));
+        assert_eq!(exit, Some(TrayAction::Exit));
+        assert_eq!(invalid, None);
+    }
+
+    #[test]
+    fn test_plan_tray_update_only_rebuilds_for_new_items() {
+        // arrange
+        let config = ConnectionConfig::Service {
+            host: "mqtt.lab".into(),
+            port: 1883,
+            protocol: Default::default(),
+            probe: None,
+        };
+        let connection = Connection::new("mqtt".into(), config.clone(), 0);
+        let id = connection.id;
+        let connections = vec![connection];
+        let with_result = |result: HealthResult| {
+            let latest = HashMap::from([(id, result)]);
+            TraySnapshot::new(&connections, &[], &latest)
+        };
+        let shown = with_result(HealthResult::online(id, 12));
+        let slower = with_result(HealthResult::online(id, 40));
+        let offline = with_result(HealthResult::offline(id, None));
+        let added = TraySnapshot::new(
+            &[
+                connections[0].clone(),
+                Connection::new("broker".into(), config, 1),
+            ],
+            &[],
+            &HashMap::from([(id, HealthResult::online(id, 12))]),
+        );
+
+        // act
+        let plans = [
+            plan_tray_update(&shown, &slower, false),
+            plan_tray_update(&shown, &slower, true),
+            plan_tray_update(&shown, &offline, false),
+            plan_tray_update(&shown, &added, false),
+        ];
+
+        // assert
+        assert_eq!(
+            plans,
+            [
+                TrayUpdate::Nothing,
+                TrayUpdate::Relabel,
+                TrayUpdate::Relabel,
+                TrayUpdate::Rebuild,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tray_label_shows_marker_and_latency() {
+        // arrange
+        let connection = Connection::new(
+            "nas".into(),
+            ConnectionConfig::Website {
+                url: "https://nas.local".into(),
+                check_path: None,
+                strict_tls: false,
+                check: None,
+                auth: None,
+                auth_secret: None,
+            },
+            0,
+        );
+        let id = connection.id;
+
+        // act
+        let unchecked = tray_label(&connection, None);
+        let online = tray_label(&connection, Some(&HealthResult::online(id, 42)));
+        let offline = tray_label(&connection, Some(&HealthResult::offline(id, None)));
+
+        // assert
+        assert_eq!(unchecked, "⚪ nas");
+        assert_eq!(online, "🟢 nas (42 ms)");
+        assert_eq!(offline, "🔴 nas (offline)");
+    }
+}
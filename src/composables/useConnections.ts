@@ -1,13 +1,21 @@
 import { ref, computed } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
-import type { Connection } from '@/types/connection'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type { ConfigBackup, Connection, Group } from '@/types/connection'
 
 const connections = ref<Connection[]>([])
+const groups = ref<Group[]>([])
+let unlisten: UnlistenFn | null = null
+let unlistenGroups: UnlistenFn | null = null
 
 const sortedConnections = computed(() => {
   return [...connections.value].sort((a, b) => a.order - b.order)
 })
 
+const sortedGroups = computed(() => {
+  return [...groups.value].sort((a, b) => a.order - b.order)
+})
+
 export function useConnections() {
   async function loadConnections() {
     try {
@@ -48,11 +56,119 @@ export function useConnections() {
     }
   }
 
+  async function loadGroups() {
+    try {
+      groups.value = await invoke<Group[]>('get_groups')
+    } catch (err) {
+      console.error('Failed to load groups:', err)
+      groups.value = []
+    }
+  }
+
+  async function createGroup(name: string, icon?: string) {
+    try {
+      const group = await invoke<Group>('create_group', { name, icon })
+      await loadGroups()
+      return group
+    } catch (err) {
+      console.error('Failed to create group:', err)
+      throw err
+    }
+  }
+
+  async function updateGroup(group: Group) {
+    try {
+      await invoke('update_group', { group })
+      await loadGroups()
+    } catch (err) {
+      console.error('Failed to update group:', err)
+      throw err
+    }
+  }
+
+  async function deleteGroup(id: string) {
+    try {
+      await invoke('delete_group', { id })
+      await Promise.all([loadGroups(), loadConnections()])
+    } catch (err) {
+      console.error('Failed to delete group:', err)
+      throw err
+    }
+  }
+
+  async function reorderGroups(ids: string[]) {
+    try {
+      await invoke('reorder_groups', { ids })
+      await loadGroups()
+    } catch (err) {
+      console.error('Failed to reorder groups:', err)
+      throw err
+    }
+  }
+
+  async function moveConnection(id: string, groupId: string | null, index?: number) {
+    try {
+      await invoke('move_connection', { id, groupId, index })
+      await loadConnections()
+    } catch (err) {
+      console.error('Failed to move connection:', err)
+      throw err
+    }
+  }
+
+  async function searchConnections(query: string) {
+    return invoke<Connection[]>('search_connections', { query })
+  }
+
+  async function listBackups() {
+    return invoke<ConfigBackup[]>('list_config_backups')
+  }
+
+  async function restoreBackup(name: string) {
+    try {
+      connections.value = await invoke<Connection[]>('restore_config_backup', { name })
+      await loadGroups()
+    } catch (err) {
+      console.error('Failed to restore backup:', err)
+      throw err
+    }
+  }
+
+  async function subscribe() {
+    unsubscribe()
+    unlisten = await listen<Connection[]>('connections-changed', (event) => {
+      connections.value = event.payload
+    })
+    unlistenGroups = await listen<Group[]>('groups-changed', (event) => {
+      groups.value = event.payload
+    })
+    await Promise.all([loadConnections(), loadGroups()])
+  }
+
+  function unsubscribe() {
+    unlisten?.()
+    unlisten = null
+    unlistenGroups?.()
+    unlistenGroups = null
+  }
+
   return {
     connections: sortedConnections,
+    groups: sortedGroups,
     loadConnections,
+    loadGroups,
+    createGroup,
+    updateGroup,
+    deleteGroup,
+    reorderGroups,
+    moveConnection,
     saveConnection,
     deleteConnection,
     reorderConnections,
+    searchConnections,
+    listBackups,
+    restoreBackup,
+    subscribe,
+    unsubscribe,
   }
 }
@@ -1,9 +1,11 @@
 import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
 import type { Connection, HealthResult } from '@/types/connection'
+import { errorMessage } from '@/types/error'
 
 const healthStatuses = ref<Map<string, HealthResult>>(new Map())
-let pollingInterval: ReturnType<typeof setInterval> | null = null
+let unlisteners: UnlistenFn[] = []
 
 export function useHealth() {
   async function checkHealth(connection: Connection): Promise<HealthResult> {
@@ -15,7 +17,7 @@ export function useHealth() {
       const result: HealthResult = {
         connectionId: connection.id,
         status: 'unknown',
-        error: String(err),
+        error: errorMessage(err),
         checkedAt: new Date().toISOString(),
       }
       healthStatuses.value.set(connection.id, result)
@@ -23,30 +25,51 @@ export function useHealth() {
     }
   }
 
-  async function checkAllHealth(connections: Connection[]) {
-    const results = await Promise.all(connections.map((c) => checkHealth(c)))
+  async function checkAllHealth() {
+    const results = await invoke<HealthResult[]>('check_all_health')
+    for (const result of results) {
+      if (result.status !== 'unknown' || !healthStatuses.value.has(result.connectionId)) {
+        healthStatuses.value.set(result.connectionId, result)
+      }
+    }
     return results
   }
 
-  function startPolling(connections: Connection[], intervalMs = 30000) {
-    stopPolling()
-    pollingInterval = setInterval(() => {
-      checkAllHealth(connections)
-    }, intervalMs)
+  async function cancelHealthChecks() {
+    await invoke('cancel_health_checks')
   }
 
-  function stopPolling() {
-    if (pollingInterval) {
-      clearInterval(pollingInterval)
-      pollingInterval = null
+  async function loadHealthStatuses() {
+    const results = await invoke<HealthResult[]>('get_health_statuses')
+    for (const result of results) {
+      healthStatuses.value.set(result.connectionId, result)
     }
   }
 
+  async function subscribe() {
+    unsubscribe()
+    const update = (event: { payload: HealthResult }) => {
+      healthStatuses.value.set(event.payload.connectionId, event.payload)
+    }
+    unlisteners = await Promise.all([
+      listen<HealthResult>('health-changed', update),
+      listen<HealthResult>('health-checked', update),
+    ])
+    await loadHealthStatuses()
+  }
+
+  function unsubscribe() {
+    unlisteners.forEach((unlisten) => unlisten())
+    unlisteners = []
+  }
+
   return {
     healthStatuses,
     checkHealth,
     checkAllHealth,
-    startPolling,
-    stopPolling,
+    cancelHealthChecks,
+    loadHealthStatuses,
+    subscribe,
+    unsubscribe,
   }
 }
@@ -1,4 +1,6 @@
 import { ref, watch } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import type { AlertChannel, AlertDelivery, AppSettings } from '@/types/connection'
 
 export type Theme = 'dark' | 'waffle'
 
@@ -19,6 +21,8 @@ function applyTheme(t: Theme) {
 
 applyTheme(theme.value)
 
+const appSettings = ref<AppSettings | null>(null)
+
 export function useSettings() {
   function toggleTheme() {
     theme.value = theme.value === 'dark' ? 'waffle' : 'dark'
@@ -28,9 +32,32 @@ export function useSettings() {
     theme.value = t
   }
 
+  async function loadAppSettings() {
+    appSettings.value = await invoke<AppSettings>('get_settings')
+    return appSettings.value
+  }
+
+  async function saveAppSettings(settings: AppSettings) {
+    await invoke('save_settings', { settings })
+    appSettings.value = settings
+  }
+
+  async function sendTestAlert(channel: AlertChannel) {
+    return invoke<AlertDelivery>('send_test_alert', { channel })
+  }
+
+  async function getAlertDeliveries() {
+    return invoke<AlertDelivery[]>('get_alert_deliveries')
+  }
+
   return {
     theme,
     toggleTheme,
     setTheme,
+    appSettings,
+    loadAppSettings,
+    saveAppSettings,
+    sendTestAlert,
+    getAlertDeliveries,
   }
 }
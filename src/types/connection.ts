@@ -1,10 +1,56 @@
-export type ConnectionType = 'website' | 'ssh'
+export type ConnectionType =
+  | 'website'
+  | 'ssh'
+  | 'rdp'
+  | 'vnc'
+  | 'postgres'
+  | 'mysql'
+  | 'redis'
+  | 'service'
+  | 'command'
+  | 'serial'
 export type HealthStatus = 'online' | 'offline' | 'degraded' | 'unknown'
+export type CheckMode = 'auto' | 'icmp'
+export type StatusPolicy = 'online' | 'degraded' | 'offline'
+
+export interface BodyAssertion {
+  pattern: string
+  regex?: boolean
+}
+
+export interface JsonAssertion {
+  path: string
+  equals: unknown
+}
+
+export interface HttpCheckSpec {
+  method?: string
+  headers?: Record<string, string>
+  body?: string
+  expectedStatus?: string[]
+  bodyContains?: BodyAssertion
+  bodyExcludes?: BodyAssertion
+  json?: JsonAssertion
+  onFailure?: StatusPolicy
+}
+
+export type HttpAuth =
+  | { type: 'basic'; username: string }
+  | { type: 'bearer' }
+  | { type: 'header'; name: string }
+  | { type: 'clientCert'; certPath: string }
+
+export type HttpAuthType = HttpAuth['type']
 
 export interface WebsiteConfig {
   type: 'website'
   url: string
   checkPath?: string
+  strictTls?: boolean
+  check?: HttpCheckSpec
+  auth?: HttpAuth
+  // Write-only: sent on save and stored encrypted, never returned.
+  authSecret?: string
 }
 
 export interface SshConfig {
@@ -17,7 +63,113 @@ export interface SshConfig {
   broadcastAddr?: string
 }
 
-export type ConnectionConfig = WebsiteConfig | SshConfig
+export interface RdpConfig {
+  type: 'rdp'
+  host: string
+  port: number
+  username?: string
+  domain?: string
+  // "WIDTHxHEIGHT"; ignored when fullscreen is set.
+  resolution?: string
+  fullscreen?: boolean
+  gateway?: string
+}
+
+export interface VncConfig {
+  type: 'vnc'
+  host: string
+  // Display N listens on 5900 + N.
+  port: number
+  viewOnly?: boolean
+}
+
+export interface PostgresConfig {
+  type: 'postgres'
+  host: string
+  port: number
+  username?: string
+  database?: string
+}
+
+export interface MysqlConfig {
+  type: 'mysql'
+  host: string
+  port: number
+  username?: string
+  database?: string
+}
+
+export interface RedisConfig {
+  type: 'redis'
+  host: string
+  port: number
+  database?: number
+}
+
+export type Transport = 'tcp' | 'udp'
+
+export interface ServiceProbe {
+  send?: string
+  // Text understands \r, \n, \t, \\ and \xNN escapes.
+  sendFormat?: 'text' | 'hex'
+  expect?: string
+  expectFormat?: 'regex' | 'hex'
+}
+
+export interface ServiceConfig {
+  type: 'service'
+  host: string
+  port: number
+  protocol: Transport
+  probe?: ServiceProbe
+}
+
+// Both command lines are run by `sh -c`. The probe exits 0, 1 or 2 for
+// online, degraded or offline and its first line of output is shown.
+export interface CommandConfig {
+  type: 'command'
+  command: string
+  workingDir?: string
+  env?: Record<string, string>
+  probeCommand?: string
+  timeoutSecs?: number
+}
+
+export type Parity = 'none' | 'even' | 'odd'
+
+// Always eight data bits, no flow control.
+export interface SerialConfig {
+  type: 'serial'
+  device: string
+  baudRate: number
+  parity: Parity
+  stopBits: 1 | 2
+  openCheck?: boolean
+  keepDtr?: boolean
+}
+
+export type ConnectionConfig =
+  | WebsiteConfig
+  | SshConfig
+  | RdpConfig
+  | VncConfig
+  | PostgresConfig
+  | MysqlConfig
+  | RedisConfig
+  | ServiceConfig
+  | CommandConfig
+  | SerialConfig
+
+export interface HealthThresholds {
+  // Connect or round-trip time for every check except HTTP.
+  warnLatencyMs?: number
+  // Time to a full HTTP response; falls back to warnLatencyMs when unset.
+  slowResponseMs?: number
+  warnPacketLoss?: number
+  redirectStatus: StatusPolicy
+  clientErrorStatus: StatusPolicy
+  certExpiryWarnDays: number
+}
 
 export interface Connection {
   id: string
@@ -25,7 +177,14 @@ export interface Connection {
   icon?: string
   iconColor?: string
   order: number
+  groupId?: string
+  tags?: string[]
   config: ConnectionConfig
+  checkMode?: CheckMode
+  pingCount?: number
+  checkIntervalSecs?: number
+  thresholds?: HealthThresholds
+  notify?: boolean
   createdAt: string
   updatedAt: string
 }
@@ -113,10 +272,117 @@ export const ICON_COLORS = [
   '#64748b',
 ] as const
 
+export interface PingStats {
+  sent: number
+  received: number
+  packetLoss: number
+  minMs?: number
+  avgMs?: number
+  maxMs?: number
+  jitterMs?: number
+}
+
+export interface TlsCertificate {
+  subject: string
+  issuer: string
+  notBefore: string
+  notAfter: string
+}
+
+export interface TlsInfo {
+  chain: TlsCertificate[]
+  issuer: string
+  subjectAltNames: string[]
+  daysUntilExpiry: number
+  validationError?: string
+}
+
+export interface HttpTiming {
+  dnsMs: number
+  connectMs: number
+  tlsMs?: number
+  ttfbMs: number
+  downloadMs: number
+  totalMs: number
+  redirects?: number
+}
+
 export interface HealthResult {
   connectionId: string
   status: HealthStatus
   latencyMs?: number
   error?: string
+  ping?: PingStats
+  tls?: TlsInfo
+  timing?: HttpTiming
+  message?: string
   checkedAt: string
 }
+
+export type HistoryWindow = '24h' | '7d' | '30d'
+
+export interface HealthStats {
+  window: HistoryWindow
+  uptimePercent?: number
+  samples: number
+  p50Ms?: number
+  p95Ms?: number
+  p99Ms?: number
+}
+
+export interface Outage {
+  startedAt: string
+  endedAt?: string
+  durationSecs: number
+}
+
+export interface Group {
+  id: string
+  name: string
+  icon?: string
+  collapsed: boolean
+  order: number
+}
+
+export interface ConfigBackup {
+  name: string
+  createdAt: string
+  sizeBytes: number
+}
+
+export interface QuietHours {
+  start: string
+  end: string
+}
+
+export interface NotificationSettings {
+  failureThreshold: number
+  quietHours?: QuietHours
+}
+
+export interface AlertChannel {
+  id: string
+  name: string
+  enabled: boolean
+  url: string
+  method: string
+  headers: Record<string, string>
+  bodyTemplate: string
+}
+
+export interface AlertDelivery {
+  channelId: string
+  channelName: string
+  connectionId?: string
+  attemptedAt: string
+  attempts: number
+  delivered: boolean
+  statusCode?: number
+  error?: string
+  test: boolean
+}
+
+export interface AppSettings {
+  notifications: NotificationSettings
+  alertChannels: AlertChannel[]
+}
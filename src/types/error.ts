@@ -0,0 +1,60 @@
+export type ErrorCode =
+  | 'invalid_id'
+  | 'connection_not_found'
+  | 'invalid_probe'
+  | 'no_config_dir'
+  | 'storage_io'
+  | 'storage_corrupt'
+  | 'secrets_unreadable'
+  | 'secrets_key_missing'
+  | 'backup_not_found'
+  | 'config_too_new'
+  | 'config_unreadable'
+  | 'invalid_mac_address'
+  | 'mac_not_found'
+  | 'wol_send_failed'
+  | 'wol_not_enabled'
+  | 'missing_mac_address'
+  | 'wol_unsupported'
+  | 'no_terminal'
+  | 'terminal_launch_failed'
+  | 'no_remote_client'
+  | 'remote_launch_failed'
+  | 'no_serial_client'
+  | 'not_openable'
+  | 'open_failed'
+
+export interface CommandError {
+  code: ErrorCode
+  message: string
+  details?: string
+}
+
+export function isCommandError(err: unknown): err is CommandError {
+  return typeof err === 'object' && err !== null && 'code' in err && 'message' in err
+}
+
+export function errorMessage(err: unknown): string {
+  if (isCommandError(err)) return err.message
+  return err instanceof Error ? err.message : String(err)
+}
+
+const hints: Partial<Record<ErrorCode, string>> = {
+  no_terminal: 'Install a supported terminal such as gnome-terminal, konsole, kitty or alacritty.',
+  no_remote_client: 'Install FreeRDP or Remmina for RDP, or TigerVNC or Remmina for VNC.',
+  no_serial_client: 'Install picocom, minicom or screen.',
+  missing_mac_address: 'Edit the connection and set its MAC address.',
+  invalid_mac_address: 'Edit the connection and use the format AA:BB:CC:DD:EE:FF.',
+  wol_not_enabled: 'Edit the connection and enable Wake-on-LAN.',
+  mac_not_found: 'Make sure the host is online and on the local network, or enter the MAC address by hand.',
+  invalid_probe: 'Check the probe: patterns are regular expressions, and hex needs pairs of digits.',
+  config_too_new: 'Update Turbo Waffle to open this configuration.',
+  config_unreadable:
+    'Restore one of the backups below, or fix connections.json by hand and restart. Restoring keeps a copy of the current file.',
+  secrets_unreadable: 'Re-enter the credentials for connections that use authentication.',
+  secrets_key_missing: 'Restore secrets.key from a backup if the OS keyring is not in use, or re-enter the credentials for connections that use authentication.',
+}
+
+export function errorHint(err: unknown): string | undefined {
+  return isCommandError(err) ? hints[err.code] : undefined
+}